@@ -3,23 +3,29 @@ extern crate amethyst_servo;
 extern crate genmesh;
 extern crate hibitset;
 
+use amethyst::assets::Directory;
 use amethyst::core::cgmath::{Deg, Matrix4};
 use amethyst::core::transform::GlobalTransform;
 use amethyst::prelude::*;
 use amethyst::renderer::*;
-use amethyst_servo::{ServoBlit, ServoPass, ServoSize, ServoUiBundle, ServoUrl};
+use amethyst_servo::{ServoAssets, ServoPass, ServoPrefab, ServoUiBundle};
 
 struct Example;
 
 impl State for Example {
     fn on_start(&mut self, world: &mut World) {
         println!("Create servo");
-        let material = { world.read_resource::<MaterialDefaults>().0.clone() };
+        let assets = format!("{}/examples/assets/", env!("CARGO_MANIFEST_DIR"));
         world
-            .create_entity()
-            .with(ServoBlit {})
-            .with(material)
-            .build();
+            .write_resource::<ServoAssets>()
+            .add_source("", Directory::new(assets));
+        let panels = format!(
+            "{}/examples/blit/resources/panels.ron",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        for panel in Vec::<ServoPrefab>::load(&panels) {
+            panel.create_entity(world);
+        }
     }
 
     fn handle_event(&mut self, _: &mut World, event: Event) -> Trans {
@@ -56,7 +62,7 @@ fn run() -> Result<(), amethyst::Error> {
     );
     let mut game = Application::build(&resources, Example)?
         .with_bundle(RenderBundle::new(pipe, Some(config)))?
-        .with_bundle(ServoUiBundle {})?
+        .with_bundle(ServoUiBundle::new())?
         .build()?;
     game.run();
     Ok(())
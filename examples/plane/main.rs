@@ -3,41 +3,43 @@ extern crate amethyst_servo;
 extern crate genmesh;
 extern crate hibitset;
 
-use amethyst::assets::Loader;
+use amethyst::assets::{Directory, Loader};
 use amethyst::core::cgmath::{Deg, Matrix4, Vector3};
 use amethyst::core::transform::GlobalTransform;
 use amethyst::prelude::*;
 use amethyst::renderer::*;
 use genmesh::{MapToVertices, Triangulate, Vertex, Vertices};
 use genmesh::generators::Plane;
-use amethyst_servo::{ServoSize, ServoUiBundle, ServoUrl};
+use amethyst_servo::{ServoAssets, ServoConfig, ServoRaycastTarget, ServoSize, ServoUiBundle,
+                     ServoUrl};
 
 struct Example;
 
 impl State for Example {
     fn on_start(&mut self, world: &mut World) {
         println!("Create servo");
+        let assets = format!("{}/examples/assets/", env!("CARGO_MANIFEST_DIR"));
+        world
+            .write_resource::<ServoAssets>()
+            .add_source("", Directory::new(assets));
+        let verts = gen_plane();
+        let raycast_target = ServoRaycastTarget::from(&verts[..]);
         let mesh = {
-            let verts = gen_plane().into();
             let loader = world.read_resource::<Loader>();
             let meshes = world.read_resource();
 
-            let mesh: MeshHandle = loader.load_from_data(verts, (), &meshes);
+            let mesh: MeshHandle = loader.load_from_data(verts.into(), (), &meshes);
             mesh
         };
         let material = world.read_resource::<MaterialDefaults>().0.clone();
         let pos = Matrix4::from_translation([0., 0., 0.].into());
         world
             .create_entity()
-            .with::<ServoUrl>(
-                format!(
-                    "file://{}/examples/assets/test.html",
-                    env!("CARGO_MANIFEST_DIR")
-                ).into(),
-            )
+            .with::<ServoUrl>("amethyst:///test.html".into())
             .with::<ServoSize>((1024, 1024).into())
             .with(GlobalTransform(pos.into()))
             .with(mesh.clone())
+            .with(raycast_target)
             .with(material)
             .build();
 
@@ -83,6 +85,11 @@ fn run() -> Result<(), amethyst::Error> {
         env!("CARGO_MANIFEST_DIR")
     );
     let config = DisplayConfig::load(&path);
+    let servo_path = format!(
+        "{}/examples/plane/resources/servo_config.ron",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let servo_config = ServoConfig::load(&servo_path);
 
     let resources = format!("{}/examples/assets/", env!("CARGO_MANIFEST_DIR"));
 
@@ -93,7 +100,7 @@ fn run() -> Result<(), amethyst::Error> {
     );
     let mut game = Application::build(&resources, Example)?
         .with_bundle(RenderBundle::new(pipe, Some(config)))?
-        .with_bundle(ServoUiBundle {})?
+        .with_bundle(ServoUiBundle::new().with_config(servo_config))?
         .build()?;
     game.run();
     Ok(())
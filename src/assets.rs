@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path};
+use std::sync::{Arc, RwLock};
+use std::time::UNIX_EPOCH;
+use amethyst::assets::Source;
+
+use super::bridge::decode;
+
+/// Asset sources shared with the bridge server, by name
+pub type Sources = Arc<RwLock<HashMap<String, Arc<Source>>>>;
+
+/// Resource mapping `amethyst://` URLs to asset sources. `amethyst:///ui/index.html` loads
+/// `ui/index.html` from the default source, `amethyst://packed/ui/index.html` loads it from the
+/// source named `packed`.
+pub struct ServoAssets {
+    scheme: String,
+    pub(crate) sources: Sources,
+}
+
+impl Default for ServoAssets {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServoAssets {
+    pub fn new() -> Self {
+        Self {
+            scheme: "amethyst".to_string(),
+            sources: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Source used by URLs without a host, usually the asset root of the application
+    pub fn with_default_source<S: Source>(self, source: S) -> Self {
+        self.with_source("", source)
+    }
+
+    pub fn with_source<S: Source>(mut self, name: &str, source: S) -> Self {
+        self.add_source(name, source);
+        self
+    }
+
+    pub fn with_scheme(mut self, scheme: &str) -> Self {
+        self.scheme = scheme.to_string();
+        self
+    }
+
+    pub fn add_source<S: Source>(&mut self, name: &str, source: S) {
+        match self.sources.write() {
+            Ok(mut sources) => {
+                sources.insert(name.to_string(), Arc::new(source));
+            }
+            Err(_) => eprintln!("ERROR: Asset sources lock poisoned."),
+        }
+    }
+
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// Turns an asset URL into the bridge URL serving it, other URLs are returned unchanged
+    pub fn resolve(&self, url: &str, port: u16) -> String {
+        let prefix = format!("{}://", self.scheme);
+        if !url.starts_with(&prefix) {
+            return url.to_string();
+        }
+        let mut parts = url[prefix.len()..].splitn(2, '/');
+        let source = parts.next().unwrap_or("");
+        let path = parts.next().unwrap_or("");
+        format!("http://127.0.0.1:{}/assets/{}/{}", port, source, path)
+    }
+
+    /// Inverse of resolve, so the game sees the asset URLs it navigated to
+    pub fn unresolve(&self, url: &str, port: u16) -> String {
+        let prefix = format!("http://127.0.0.1:{}/assets/", port);
+        if !url.starts_with(&prefix) {
+            return url.to_string();
+        }
+        format!("{}://{}", self.scheme, &url[prefix.len()..])
+    }
+
+    /// Modification time of the file behind an asset or `file://` URL, in seconds since the
+    /// Unix epoch. None for other URLs and files that can't be read.
+    pub fn modified(&self, url: &str) -> Option<u64> {
+        let url = url.split(|c| c == '?' || c == '#').next().unwrap_or("");
+        let prefix = format!("{}://", self.scheme);
+        if url.starts_with(&prefix) {
+            let url = decode(&url[prefix.len()..]);
+            let (source, path) = match split_asset(&url) {
+                Some(asset) => asset,
+                None => return None,
+            };
+            let source = match self.sources.read() {
+                Ok(sources) => sources.get(source).cloned(),
+                Err(_) => None,
+            };
+            return source.and_then(|source| source.modified(path).ok());
+        }
+        if url.starts_with("file://") {
+            let path = decode(&url["file://".len()..]);
+            return fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs());
+        }
+        None
+    }
+}
+
+/// Splits a decoded `source/path` into the source name and the path within it. None when the
+/// path could leave the source, as with `..`, a drive prefix or a leading slash or backslash.
+pub(crate) fn split_asset(asset: &str) -> Option<(&str, &str)> {
+    let mut parts = asset.splitn(2, '/');
+    let source = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("");
+    if path.starts_with('/') || path.starts_with('\\') {
+        return None;
+    }
+    let contained = path.split(|c| c == '/' || c == '\\').all(|part| {
+        Path::new(part).components().all(|component| match component {
+            Component::Normal(_) => true,
+            _ => false,
+        })
+    });
+    if contained {
+        Some((source, path))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,43 @@
+use std::collections::HashSet;
+use amethyst::renderer::Texture;
+use libservo::embedder_traits::EmbedderMsg;
+use libservo::msg::constellation_msg::TopLevelBrowsingContextId;
+
+use super::{Bridge, ServoAssets, ServoError, ServoHandle, ServoImage};
+use super::handle::BrowserEvent;
+
+/// What the ServoUiSystem asks of the browser engine, implemented by ServoEngine. Other
+/// implementations let the system run without Servo or a GL context, such as the MockBackend
+/// of the `testing` feature.
+pub trait ServoBackend {
+    /// HiDPI factor of whatever the pages are shown on
+    fn hidpi_factor(&self) -> f32;
+    /// Starts a browser loading `url`, the handle is made with ServoHandle::new
+    fn new_browser(&mut self, assets: &ServoAssets, url: &str) -> Result<ServoHandle, ServoError>;
+    /// Renders the browser into the texture of its Material
+    fn setup_target(
+        &self,
+        id: TopLevelBrowsingContextId,
+        texture: &Texture,
+    ) -> Result<(), ServoError>;
+    /// Renders the browser into a texture of its own
+    fn setup_offscreen_target(&self, id: TopLevelBrowsingContextId) -> Result<(), ServoError>;
+    /// Closes every browser that is not in `alive`
+    fn close_browsers(&mut self, alive: &HashSet<TopLevelBrowsingContextId>);
+    /// Hands the events to the browser and composites it into its target. The system starts
+    /// the events with Select, Resize and zoom events when the compositor has to be switched
+    /// to the browser.
+    fn handle_events(&mut self, id: TopLevelBrowsingContextId, events: Vec<BrowserEvent>);
+    /// Lets the engine process its messages without compositing a particular browser
+    fn pump(&mut self);
+    fn capture(&self, id: TopLevelBrowsingContextId) -> Result<ServoImage, ServoError>;
+    /// Messages from the engine since the last call, by the browser they concern
+    fn take_events(&mut self) -> Vec<(Option<TopLevelBrowsingContextId>, EmbedderMsg)>;
+    /// Connection to the script injected into pages, started with Bridge::start. None if
+    /// scripts aren't supported.
+    fn bridge(&self) -> Option<&Bridge>;
+    /// URL the engine should load for `url`
+    fn resolve_url(&self, assets: &ServoAssets, url: &str) -> String;
+    /// URL the game should see for a URL the engine loaded
+    fn unresolve_url(&self, assets: &ServoAssets, url: &str) -> String;
+}
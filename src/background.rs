@@ -0,0 +1,30 @@
+use amethyst::ecs::{Component, VecStorage};
+
+/// Color the page is composited over, as straight RGBA. Entities without it get an opaque
+/// white background. A transparent background shows the game through the parts of the page
+/// that don't paint their own background, which needs `background: transparent` on the
+/// root element of the page.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ServoBackground {
+    pub color: [f32; 4],
+}
+
+impl Component for ServoBackground {
+    type Storage = VecStorage<ServoBackground>;
+}
+
+impl Default for ServoBackground {
+    fn default() -> Self {
+        Self::new([1., 1., 1., 1.])
+    }
+}
+
+impl ServoBackground {
+    pub fn new(color: [f32; 4]) -> Self {
+        Self { color: color }
+    }
+
+    pub fn transparent() -> Self {
+        Self::new([0., 0., 0., 0.])
+    }
+}
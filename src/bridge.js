@@ -0,0 +1,118 @@
+// Injected into every page by amethyst_servo, connects the page to the game
+(function () {
+    if (window.amethyst) {
+        return;
+    }
+    var base = "http://127.0.0.1:{{PORT}}";
+    // Only the game's own pages are connected unless ServoConfig::remote_scripts is set
+    if (!{{REMOTE}} && location.protocol !== "file:" && location.origin !== base) {
+        return;
+    }
+    // The game only listens to scripts that know the secret it injected
+    var secret = "{{SECRET}}";
+    var random = new Uint32Array(4);
+    window.crypto.getRandomValues(random);
+    var token = Array.prototype.map.call(random, function (n) {
+        return n.toString(36);
+    }).join("");
+    var path = secret + "/" + token;
+
+    function send(message) {
+        var request = new XMLHttpRequest();
+        request.open("POST", base + "/send/" + path);
+        request.setRequestHeader("Content-Type", "text/plain");
+        request.send(JSON.stringify(message));
+    }
+
+    function reply(id, ok, value) {
+        try {
+            send({ type: "result", id: id, ok: ok, value: value === undefined ? null : value });
+        } catch (e) {
+            // The value could not be serialized
+            send({ type: "result", id: id, ok: false, value: String(e) });
+        }
+    }
+
+    function evaluate(command) {
+        var value;
+        try {
+            value = (0, eval)(command.script);
+        } catch (e) {
+            reply(command.id, false, String(e));
+            return;
+        }
+        if (value && typeof value.then === "function") {
+            value.then(function (v) {
+                reply(command.id, true, v);
+            }, function (e) {
+                reply(command.id, false, String(e));
+            });
+        } else {
+            reply(command.id, true, value);
+        }
+    }
+
+    // Points the stylesheets at fresh copies, the rest of the page keeps its state
+    function reloadStylesheets(urls) {
+        var links = document.querySelectorAll("link[rel=stylesheet]");
+        for (var i = 0; i < links.length; i++) {
+            var href = links[i].href.split(/[?#]/)[0];
+            if (urls.indexOf(href) !== -1) {
+                links[i].href = href + "?reload=" + Date.now();
+            }
+        }
+    }
+
+    // Files the page was built from, watched by the game when hot reloading is enabled
+    function reportResources() {
+        var urls = [location.href];
+        var elements = document.querySelectorAll("link[href], script[src], img[src]");
+        for (var i = 0; i < elements.length; i++) {
+            urls.push(elements[i].href || elements[i].src);
+        }
+        send({ type: "resources", urls: urls });
+    }
+
+    function handle(command) {
+        switch (command.type) {
+            case "eval":
+                evaluate(command);
+                break;
+            case "message":
+                window.dispatchEvent(new CustomEvent("amethyst-message", { detail: command.data }));
+                break;
+            case "reload-css":
+                reloadStylesheets(command.urls);
+                break;
+        }
+    }
+
+    function poll() {
+        var request = new XMLHttpRequest();
+        request.open("GET", base + "/poll/" + path);
+        request.onload = function () {
+            JSON.parse(request.responseText).forEach(handle);
+            poll();
+        };
+        request.onerror = function () {
+            setTimeout(poll, 1000);
+        };
+        request.send();
+    }
+
+    window.amethyst = {
+        // Delivered to the game as a ServoMessage
+        postMessage: function (data) {
+            send({ type: "message", data: data === undefined ? null : data });
+        }
+    };
+    // The embedder only learns which browser a token belongs to through a navigation, it is
+    // cancelled before it reaches the page
+    window.location.href = "{{SCHEME}}:connect/" + path;
+    poll();
+    if (document.readyState === "complete") {
+        reportResources();
+    } else {
+        window.addEventListener("load", reportResources);
+    }
+})();
@@ -0,0 +1,419 @@
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::ServoAssets;
+use super::assets::{split_asset, Sources};
+
+/// Scheme of the navigations pages use to introduce themselves, they are never allowed
+/// through to Servo
+pub const BRIDGE_SCHEME: &str = "amethyst-bridge";
+
+const BRIDGE_SRC: &str = include_str!("bridge.js");
+const POLL_TIMEOUT: u64 = 10;
+const JSON: &str = "application/json";
+
+/// Servo has no API to run scripts in a page, so pages are given a script that talks to this
+/// loopback HTTP server. The game queues commands per page token which the page long polls
+/// for, and the page posts its replies back. It also serves the ServoAssets sources, as Servo
+/// has no way to register a protocol handler either.
+pub struct Bridge {
+    port: u16,
+    // Whether pages from any origin may connect, not just file:// pages and the assets
+    remote: bool,
+    // Only known to the injected script, pages prove with it that they were given the script
+    secret: String,
+    outbound: Arc<(Mutex<HashMap<String, Vec<String>>>, Condvar)>,
+    inbound: Receiver<(String, String)>,
+    // Cleared to stop the listener, which then closes the socket
+    running: Arc<AtomicBool>,
+    listener: Option<JoinHandle<()>>,
+    // Directory of the injected script, removed when the bridge stops
+    userscripts: Option<PathBuf>,
+}
+
+impl Bridge {
+    /// Serves the sources of `assets` on a free loopback port. Pages from any origin may
+    /// connect if `remote` is set, otherwise only `file://` pages and the assets.
+    pub fn start(assets: &ServoAssets, remote: bool) -> io::Result<Bridge> {
+        let sources = assets.sources.clone();
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let outbound = Arc::new((Mutex::new(HashMap::new()), Condvar::new()));
+        let (sender, receiver) = channel();
+        let state = outbound.clone();
+        let secret = random_hex();
+        let key = secret.clone();
+        let running = Arc::new(AtomicBool::new(true));
+        let listening = running.clone();
+        let listener = thread::Builder::new()
+            .name("amethyst_servo bridge".into())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if !listening.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    match stream {
+                        Ok(stream) => {
+                            let state = state.clone();
+                            let sender = sender.clone();
+                            let sources = sources.clone();
+                            let key = key.clone();
+                            thread::spawn(move || {
+                                let served =
+                                    serve(stream, port, remote, &key, &state, &sender, &sources);
+                                if let Err(e) = served {
+                                    eprintln!("Bridge request failed: {}", e);
+                                }
+                            });
+                        }
+                        Err(e) => eprintln!("Bridge connection failed: {}", e),
+                    }
+                }
+            })?;
+        Ok(Bridge {
+            port: port,
+            remote: remote,
+            secret: secret,
+            outbound: outbound,
+            inbound: receiver,
+            running: running,
+            listener: Some(listener),
+            userscripts: None,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub(crate) fn secret(&self) -> &str {
+        &self.secret
+    }
+
+    /// Whether pages from any origin may connect
+    pub fn remote(&self) -> bool {
+        self.remote
+    }
+
+    /// Token of the page introducing itself with `url`, None if `url` is not a connection
+    /// from the injected script
+    pub fn connect_token(&self, url: &str) -> Option<String> {
+        let connect = format!("{}:connect/", BRIDGE_SCHEME);
+        if url.starts_with(&connect) {
+            authorize(&url[connect.len()..], &self.secret).map(|token| token.to_string())
+        } else {
+            None
+        }
+    }
+
+    /// Writes the page side of the bridge into a directory Servo can load user scripts from.
+    /// The directory is only readable by the user and is removed when the bridge is dropped.
+    pub fn write_userscripts(&mut self) -> io::Result<PathBuf> {
+        let dir = ::std::env::temp_dir().join(format!("amethyst_servo_{}", random_hex()));
+        create_private_dir(&dir)?;
+        self.userscripts = Some(dir.clone());
+        let script = BRIDGE_SRC
+            .replace("{{PORT}}", &self.port.to_string())
+            .replace("{{REMOTE}}", &self.remote.to_string())
+            .replace("{{SCHEME}}", BRIDGE_SCHEME)
+            .replace("{{SECRET}}", &self.secret);
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(dir.join("00.amethyst.js"))?
+            .write_all(script.as_bytes())?;
+        Ok(dir)
+    }
+
+    /// Queues a JSON command for the page owning `token`
+    pub fn send(&self, token: &str, command: String) {
+        let &(ref lock, ref condvar) = &*self.outbound;
+        match lock.lock() {
+            Ok(mut outbound) => {
+                outbound
+                    .entry(token.to_string())
+                    .or_insert_with(Vec::new)
+                    .push(command);
+                condvar.notify_all();
+            }
+            Err(_) => eprintln!("ERROR: Bridge lock poisoned."),
+        }
+    }
+
+    /// Drops the commands queued for a page that went away
+    pub fn forget(&self, token: &str) {
+        let &(ref lock, _) = &*self.outbound;
+        if let Ok(mut outbound) = lock.lock() {
+            outbound.remove(token);
+        }
+    }
+
+    /// Messages posted by pages since the last call, as (token, JSON) pairs
+    pub fn receive(&self) -> Vec<(String, String)> {
+        self.inbound.try_iter().collect()
+    }
+}
+
+impl Drop for Bridge {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        // Wakes the listener blocked in accept, so it sees it has to stop
+        let _ = TcpStream::connect(("127.0.0.1", self.port));
+        if let Some(listener) = self.listener.take() {
+            if listener.join().is_err() {
+                eprintln!("Bridge listener panicked");
+            }
+        }
+        if let Some(dir) = self.userscripts.take() {
+            if let Err(e) = fs::remove_dir_all(&dir) {
+                eprintln!("Failed to remove {}: {}", dir.display(), e);
+            }
+        }
+    }
+}
+
+fn serve(
+    stream: TcpStream,
+    port: u16,
+    remote: bool,
+    secret: &str,
+    outbound: &Arc<(Mutex<HashMap<String, Vec<String>>>, Condvar)>,
+    inbound: &Sender<(String, String)>,
+    sources: &Sources,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut content_length = 0;
+    let mut origin = None;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        let mut parts = header.splitn(2, ':');
+        let name = parts.next().unwrap_or("").trim().to_lowercase();
+        if name == "content-length" {
+            content_length = parts.next().unwrap_or("").trim().parse().unwrap_or(0);
+        } else if name == "origin" {
+            origin = Some(parts.next().unwrap_or("").trim().to_string());
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("").split('?').next().unwrap_or("");
+    let mut segments = path.trim_left_matches('/').splitn(2, '/');
+    let (route, rest) = (segments.next(), segments.next().unwrap_or(""));
+    let allowed = match origin {
+        Some(ref origin) if allow_origin(origin, port, remote) => Some(origin.clone()),
+        _ => None,
+    };
+    // Scripts prove with the secret that the game injected them, the origin alone proves
+    // nothing as sandboxed frames send "null" as well. file:// pages send "null" and
+    // same-origin requests none at all.
+    let page = origin
+        .as_ref()
+        .map_or(true, |origin| origin == "null" || allowed.is_some());
+    let token = if page { authorize(rest, secret) } else { None };
+    match (method, route, token) {
+        ("OPTIONS", _, _) => respond(stream, allowed, "204 No Content", JSON, b""),
+        ("GET", Some("poll"), Some(token)) => {
+            let commands = poll(outbound, token);
+            let body = format!("[{}]", commands.join(","));
+            respond(stream, origin, "200 OK", JSON, body.as_bytes())
+        }
+        ("POST", Some("send"), Some(token)) => {
+            let body = String::from_utf8_lossy(&body).into_owned();
+            if let Err(e) = inbound.send((token.to_string(), body)) {
+                eprintln!("Failed to receive page message: {}", e);
+            }
+            respond(stream, origin, "204 No Content", JSON, b"")
+        }
+        ("GET", Some("poll"), None) | ("POST", Some("send"), None) => {
+            respond(stream, None, "403 Forbidden", JSON, b"")
+        }
+        ("GET", Some("assets"), _) => {
+            let asset = decode(rest);
+            let found = split_asset(&asset).and_then(|(source, path)| {
+                let source = match sources.read() {
+                    Ok(sources) => sources.get(source).cloned(),
+                    Err(_) => None,
+                };
+                source.map(|source| (source, path))
+            });
+            match found {
+                Some((source, path)) => match source.load(path) {
+                    Ok(data) => respond(stream, allowed, "200 OK", content_type(path), &data),
+                    Err(e) => {
+                        eprintln!("Failed to load asset {}: {}", path, e);
+                        respond(stream, allowed, "404 Not Found", JSON, b"")
+                    }
+                },
+                None => respond(stream, allowed, "404 Not Found", JSON, b""),
+            }
+        }
+        _ => respond(stream, allowed, "404 Not Found", JSON, b""),
+    }
+}
+
+// Pages served by the bridge may read from it, other pages only when remote scripts are
+// enabled
+fn allow_origin(origin: &str, port: u16, remote: bool) -> bool {
+    remote || origin == format!("http://127.0.0.1:{}", port)
+}
+
+// Token of a `<secret>/<token>` path, if the secret is the bridge's
+fn authorize<'a>(path: &'a str, secret: &str) -> Option<&'a str> {
+    let mut parts = path.splitn(2, '/');
+    match (parts.next(), parts.next()) {
+        (Some(given), Some(token)) if given == secret && !token.is_empty() => Some(token),
+        _ => None,
+    }
+}
+
+// Fails if the directory exists, so another user can't prepare it for us
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    fs::DirBuilder::new().mode(0o700).create(dir)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    fs::create_dir(dir)
+}
+
+// Unguessable hex string, std seeds the keys of every RandomState from the OS
+fn random_hex() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.subsec_nanos())
+        .unwrap_or(0);
+    (0..2)
+        .map(|i| {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u32(process::id());
+            hasher.write_u32(nanos);
+            hasher.write_u32(i);
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+// Waits until commands are queued for the page, or the poll times out
+fn poll(
+    outbound: &Arc<(Mutex<HashMap<String, Vec<String>>>, Condvar)>,
+    token: &str,
+) -> Vec<String> {
+    let &(ref lock, ref condvar) = &**outbound;
+    let mut queue = match lock.lock() {
+        Ok(queue) => queue,
+        Err(_) => return vec![],
+    };
+    loop {
+        if let Some(commands) = queue.remove(token) {
+            return commands;
+        }
+        match condvar.wait_timeout(queue, Duration::from_secs(POLL_TIMEOUT)) {
+            Ok((guard, result)) => {
+                queue = guard;
+                if result.timed_out() {
+                    return queue.remove(token).unwrap_or_else(Vec::new);
+                }
+            }
+            Err(_) => return vec![],
+        }
+    }
+}
+
+fn respond(
+    mut stream: TcpStream,
+    origin: Option<String>,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    write!(stream, "HTTP/1.1 {}\r\n", status)?;
+    if let Some(origin) = origin {
+        write!(
+            stream,
+            "Access-Control-Allow-Origin: {}\r\n\
+             Access-Control-Allow-Methods: GET, POST, OPTIONS\r\n\
+             Access-Control-Allow-Headers: Content-Type\r\n\
+             Vary: Origin\r\n",
+            origin
+        )?;
+    }
+    write!(
+        stream,
+        "Content-Type: {}\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n",
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+fn content_type(path: &str) -> &'static str {
+    match path.rsplit('.').next().unwrap_or("") {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" => "application/javascript; charset=utf-8",
+        "json" => JSON,
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        _ => "application/octet-stream",
+    }
+}
+
+// Percent decodes a URL path
+pub(crate) fn decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = if bytes[i] == b'%' && i + 2 < bytes.len() {
+            match ((bytes[i + 1] as char).to_digit(16), (bytes[i + 2] as char).to_digit(16)) {
+                (Some(high), Some(low)) => Some((high * 16 + low) as u8),
+                _ => None,
+            }
+        } else {
+            None
+        };
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -1,19 +1,55 @@
 use amethyst::core::bundle::{ECSBundle, Result};
 use amethyst::ecs::DispatcherBuilder;
 use amethyst::prelude::World;
-use super::{ServoBlit, ServoHandle, ServoSize, ServoUiSystem, ServoUrl};
+use amethyst::shrev::EventChannel;
+use super::{ServoAssets, ServoBackground, ServoBlit, ServoConfig, ServoEvent, ServoFocus,
+            ServoFocusSystem, ServoHandle, ServoMesh, ServoMessage, ServoRaycastTarget, ServoSize,
+            ServoStatus, ServoUiSystem, ServoUrl, ServoVisibility, ServoZoom};
+
+pub struct ServoUiBundle {
+    config: ServoConfig,
+}
+
+impl ServoUiBundle {
+    pub fn new() -> Self {
+        Self {
+            config: ServoConfig::default(),
+        }
+    }
+
+    pub fn with_config(mut self, config: ServoConfig) -> Self {
+        self.config = config;
+        self
+    }
+}
 
-pub struct ServoUiBundle;
 impl<'a, 'b> ECSBundle<'a, 'b> for ServoUiBundle {
     fn build(
         self,
         world: &mut World,
         dispatcher: DispatcherBuilder<'a, 'b>,
     ) -> Result<DispatcherBuilder<'a, 'b>> {
-        world.register::<ServoUrl>();
-        world.register::<ServoHandle>();
-        world.register::<ServoSize>();
-        world.register::<ServoBlit>();
-        Ok(dispatcher.add_thread_local(ServoUiSystem::new(world)))
+        register(world);
+        Ok(dispatcher
+            .add(ServoFocusSystem::new(world), "servo_focus", &[])
+            .add_thread_local(ServoUiSystem::new(world, self.config)))
     }
 }
+
+/// Registers the components and resources the ServoUiSystem needs
+pub(crate) fn register(world: &mut World) {
+    world.register::<ServoUrl>();
+    world.register::<ServoHandle>();
+    world.register::<ServoSize>();
+    world.register::<ServoBlit>();
+    world.register::<ServoRaycastTarget>();
+    world.register::<ServoStatus>();
+    world.register::<ServoVisibility>();
+    world.register::<ServoBackground>();
+    world.register::<ServoZoom>();
+    world.register::<ServoMesh>();
+    world.add_resource(ServoFocus::default());
+    world.add_resource(ServoAssets::new());
+    world.add_resource(EventChannel::<ServoEvent>::new());
+    world.add_resource(EventChannel::<ServoMessage>::new());
+}
@@ -0,0 +1,84 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use png::{self, HasParameters};
+
+use super::ServoError;
+
+/// RGBA pixels of a page, 8 bits per channel with the top row first
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServoImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl ServoImage {
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width, self.height);
+        encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.data))
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{}", e)))
+    }
+
+    /// Loads an 8 bit RGB or RGBA PNG, such as one written by save_png
+    pub fn load_png<P: AsRef<Path>>(path: P) -> io::Result<ServoImage> {
+        let decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        let (info, mut reader) = decoder.read_info().map_err(decoding_error)?;
+        let mut buffer = vec![0; info.buffer_size()];
+        reader.next_frame(&mut buffer).map_err(decoding_error)?;
+        let data = match (info.color_type, info.bit_depth) {
+            (png::ColorType::RGBA, png::BitDepth::Eight) => buffer,
+            (png::ColorType::RGB, png::BitDepth::Eight) => buffer
+                .chunks(3)
+                .flat_map(|rgb| vec![rgb[0], rgb[1], rgb[2], 255])
+                .collect(),
+            (color, depth) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unsupported PNG format {:?} {:?}", color, depth),
+                ))
+            }
+        };
+        Ok(ServoImage {
+            width: info.width,
+            height: info.height,
+            data: data,
+        })
+    }
+}
+
+fn decoding_error(e: png::DecodingError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{}", e))
+}
+
+pub(crate) type CaptureSlot = Arc<Mutex<Option<Result<ServoImage, ServoError>>>>;
+
+/// Result of ServoHandle::capture, filled in by the ServoUiSystem after the page has been
+/// composited
+pub struct ServoCapture {
+    slot: CaptureSlot,
+}
+
+impl ServoCapture {
+    pub(crate) fn new() -> (Self, CaptureSlot) {
+        let slot = Arc::new(Mutex::new(None));
+        (Self { slot: slot.clone() }, slot)
+    }
+
+    /// Returns None until the page has been read back, then the image every time
+    pub fn try_get(&self) -> Option<Result<ServoImage, ServoError>> {
+        match self.slot.lock() {
+            Ok(slot) => match *slot {
+                Some(ref result) => Some(result.clone()),
+                None if Arc::strong_count(&self.slot) == 1 => Some(Err(ServoError::BrowserClosed)),
+                None => None,
+            },
+            Err(_) => Some(Err(ServoError::LockPoisoned)),
+        }
+    }
+}
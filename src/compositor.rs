@@ -0,0 +1,76 @@
+use std::collections::{HashMap, HashSet};
+use libservo::msg::constellation_msg::TopLevelBrowsingContextId;
+
+use super::ServoTarget;
+use super::handle::BrowserEvent;
+
+/// What the single Servo compositor was last set up for. Servo composites one browser at a
+/// time, so switching to another one can take a resize and restoring its zoom. Kept by the
+/// ServoUiSystem, so every ServoBackend is handed the same events.
+pub struct Compositor {
+    // Browser currently selected for compositing
+    active: Option<TopLevelBrowsingContextId>,
+    // Size and HiDPI factor the compositor was last resized to, and the ones each browser was
+    // laid out at
+    frame: Option<((u32, u32), f32)>,
+    layouts: HashMap<TopLevelBrowsingContextId, ((u32, u32), f32)>,
+    // Page and pinch zoom of the compositor, which zooms whichever browser is selected
+    zoom: (f32, f32),
+}
+
+impl Compositor {
+    pub fn new() -> Self {
+        Self {
+            active: None,
+            frame: None,
+            layouts: HashMap::new(),
+            zoom: (1., 1.),
+        }
+    }
+
+    /// Events selecting the browser and bringing the compositor to the size and zoom of its
+    /// target, to be handed to the engine before the events of the browser. The zoom is set
+    /// as the applied zoom of the target.
+    pub fn switch_to(
+        &mut self,
+        id: TopLevelBrowsingContextId,
+        target: &ServoTarget,
+    ) -> Vec<BrowserEvent> {
+        let mut events = vec![];
+        if self.active != Some(id) {
+            events.push(BrowserEvent::Select);
+            self.active = Some(id);
+        }
+        // Resizing reflows the selected page, so it is only sent when the compositor or the
+        // page is at another size. Browsers of the same size are switched without reflowing.
+        let metrics = (target.get_dimensions(), target.get_hidpi_factor());
+        if self.frame != Some(metrics) || self.layouts.get(&id) != Some(&metrics) {
+            events.push(BrowserEvent::Resize(metrics.0, metrics.1));
+            self.frame = Some(metrics);
+            self.layouts.insert(id, metrics);
+        }
+        // Zoom is restored whenever another browser was zoomed in between
+        let zoom = target.get_zoom();
+        if zoom.0 != self.zoom.0 {
+            events.push(if zoom.0 == 1. {
+                BrowserEvent::ResetZoom
+            } else {
+                BrowserEvent::Zoom(zoom.0 / self.zoom.0)
+            });
+        }
+        if zoom.1 != self.zoom.1 {
+            events.push(BrowserEvent::PinchZoom(zoom.1 / self.zoom.1));
+        }
+        self.zoom = zoom;
+        target.set_applied_zoom(zoom);
+        events
+    }
+
+    /// Forgets browsers that were closed
+    pub fn retain(&mut self, alive: &HashSet<TopLevelBrowsingContextId>) {
+        self.layouts.retain(|id, _| alive.contains(id));
+        if self.active.map_or(false, |id| !alive.contains(&id)) {
+            self.active = None;
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use libservo::servo_config::opts::Opts;
+use libservo::servo_config::prefs::{PrefValue, PREFS};
+
+/// Value of a Servo preference override
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ServoPref {
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+/// Options Servo is started with, loadable from RON with Config::load like DisplayConfig.
+/// They are applied once, when the first browser starts the engine.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServoConfig {
+    /// Directory holding the Servo resources, `resources` in the working directory by default
+    pub resources_path: Option<PathBuf>,
+    pub user_agent: Option<String>,
+    /// Preference overrides, by preference name
+    pub prefs: HashMap<String, ServoPref>,
+    /// Starts the devtools server on this port
+    pub devtools_port: Option<u16>,
+    /// Run without a compositor window, for servers and tests
+    pub headless: bool,
+    /// Print the display list of every frame
+    pub dump_display_list: bool,
+    pub dump_style_tree: bool,
+    pub dump_flow_tree: bool,
+    /// Print why layout reflows happen
+    pub relayout_event: bool,
+    /// Reload pages when the asset or `file://` files they were built from change
+    pub hot_reload: bool,
+    /// With hot_reload, swap changed stylesheets in place so the page keeps its state
+    pub hot_reload_css: bool,
+    /// Connect http and https pages to the game too, by default only asset and `file://`
+    /// pages can use window.amethyst and run scripts
+    pub remote_scripts: bool,
+}
+
+impl Default for ServoConfig {
+    fn default() -> Self {
+        Self {
+            resources_path: None,
+            user_agent: None,
+            prefs: HashMap::new(),
+            devtools_port: None,
+            headless: false,
+            dump_display_list: false,
+            dump_style_tree: false,
+            dump_flow_tree: false,
+            relayout_event: false,
+            hot_reload: false,
+            hot_reload_css: true,
+            remote_scripts: false,
+        }
+    }
+}
+
+impl ServoConfig {
+    pub fn resources_path(&self) -> PathBuf {
+        match self.resources_path {
+            Some(ref path) => path.clone(),
+            None => env::current_dir().unwrap_or_default().join("resources"),
+        }
+    }
+
+    pub(crate) fn apply(&self, opts: &mut Opts) {
+        if let Some(ref user_agent) = self.user_agent {
+            opts.user_agent = user_agent.clone().into();
+        }
+        opts.devtools_port = self.devtools_port;
+        opts.headless = self.headless;
+        opts.dump_display_list = self.dump_display_list;
+        opts.dump_style_tree = self.dump_style_tree;
+        opts.dump_flow_tree = self.dump_flow_tree;
+        opts.relayout_event = self.relayout_event;
+    }
+
+    /// Prefs are read from the resources path, so they are overridden after it is set
+    pub(crate) fn apply_prefs(&self) {
+        for (name, pref) in &self.prefs {
+            let value = match pref {
+                &ServoPref::Bool(value) => PrefValue::Boolean(value),
+                &ServoPref::Number(value) => PrefValue::Number(value),
+                &ServoPref::String(ref value) => PrefValue::String(value.clone()),
+            };
+            PREFS.set(name, value);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use libservo::{gl, Servo};
+use libservo::servo_config::resource_files::set_resources_path;
+use libservo::servo_config::opts;
+use libservo::ipc_channel::ipc;
+use libservo::servo_url::ServoUrl;
+use libservo::msg::constellation_msg::TopLevelBrowsingContextId;
+use libservo::compositing::windowing::WindowEvent;
+use libservo::embedder_traits::EmbedderMsg;
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+use amethyst::renderer::Texture;
+
+use super::{ServoAssets, ServoConfig, ServoError, ServoHandle, ServoImage, ServoTarget,
+            ServoWindow};
+use super::backend::ServoBackend;
+use super::bridge::Bridge;
+use super::host::ServoHost;
+use super::handle::BrowserEvent;
+
+const SHUTDOWN_TIMEOUT: u64 = 5;
+
+/// The single Servo instance shared by every browser entity, started when the first browser
+/// is created. It is owned by the thread local ServoUiSystem and is neither Send nor Sync.
+pub struct ServoEngine {
+    host: Rc<ServoHost>,
+    servo: Option<Servo<ServoWindow>>,
+    window: Option<Rc<ServoWindow>>,
+    // Browser whose target the window shows
+    active: Option<TopLevelBrowsingContextId>,
+    // Targets of every open browser, kept so their GL resources outlive the ServoHandle
+    browsers: HashMap<TopLevelBrowsingContextId, ServoTarget>,
+    // Connection to the script injected into pages, None if it failed to start
+    bridge: Option<Bridge>,
+    config: ServoConfig,
+}
+
+impl ServoEngine {
+    pub fn new(host: Rc<ServoHost>, config: ServoConfig) -> Self {
+        Self {
+            host: host,
+            servo: None,
+            window: None,
+            active: None,
+            browsers: HashMap::new(),
+            bridge: None,
+            config: config,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.servo.is_some()
+    }
+
+    pub fn host(&self) -> &ServoHost {
+        &*self.host
+    }
+
+    /// Closes every browser and waits for Servo to shut down its threads
+    pub fn shutdown(&mut self) {
+        let alive = HashSet::new();
+        self.close_browsers(&alive);
+        if let Some(mut servo) = self.servo.take() {
+            servo.handle_events(vec![WindowEvent::Quit]);
+            let start = Instant::now();
+            'shutdown: while start.elapsed() < Duration::from_secs(SHUTDOWN_TIMEOUT) {
+                servo.handle_events(vec![]);
+                for (_, event) in servo.get_events() {
+                    if let EmbedderMsg::Shutdown = event {
+                        break 'shutdown;
+                    }
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+        // Stops the bridge server and removes the injected script
+        self.bridge = None;
+        self.window = None;
+        self.active = None;
+    }
+
+    fn start_servo(&mut self, assets: &ServoAssets) -> Result<(), ServoError> {
+        // Fetch gl context
+        if let Err(e) = self.host.make_current() {
+            return Err(ServoError::GlContext(e));
+        }
+        let host = self.host.clone();
+        let gl = unsafe { gl::GlFns::load_with(|s| host.get_proc_address(s) as *const _) };
+
+        // Create renderer
+        let renderer = Rc::new(ServoWindow {
+            host: self.host.clone(),
+            gl: gl,
+            target: Arc::new(Mutex::new(None)),
+        });
+
+        // Get resources
+        let path = self.config.resources_path();
+        match path.to_str() {
+            Some(resources) if path.is_dir() => set_resources_path(Some(resources.to_string())),
+            _ => return Err(ServoError::ResourcesNotFound(path)),
+        }
+        self.config.apply_prefs();
+        let mut opts = opts::default_opts();
+        self.config.apply(&mut opts);
+
+        // Inject the bridge script into every page, it only connects the pages allowed to
+        let bridge = Bridge::start(assets, self.config.remote_scripts)
+            .and_then(|mut bridge| Ok((bridge.write_userscripts()?, bridge)));
+        match bridge {
+            Ok((userscripts, bridge)) => {
+                opts.userscripts = Some(userscripts.to_string_lossy().into_owned());
+                self.bridge = Some(bridge);
+            }
+            Err(e) => eprintln!("Failed to start the script bridge: {}", e),
+        }
+        opts::set_defaults(opts);
+
+        // Start servo
+        self.servo = Some(Servo::new(renderer.clone()));
+        self.window = Some(renderer);
+        Ok(())
+    }
+}
+
+impl ServoBackend for ServoEngine {
+    fn hidpi_factor(&self) -> f32 {
+        self.host.hidpi_factor()
+    }
+
+    fn new_browser(&mut self, assets: &ServoAssets, url: &str) -> Result<ServoHandle, ServoError> {
+        if self.servo.is_none() {
+            self.start_servo(assets)?;
+        }
+        let url = match ServoUrl::parse(&self.resolve_url(assets, url)) {
+            Ok(url) => url,
+            Err(e) => return Err(ServoError::InvalidUrl(format!("{}", e))),
+        };
+        let servo = match self.servo.as_mut() {
+            Some(servo) => servo,
+            None => return Err(ServoError::EngineStartup("Servo is not running".into())),
+        };
+        let (sender, receiver) = match ipc::channel() {
+            Ok(channel) => channel,
+            Err(e) => return Err(ServoError::EngineStartup(format!("{}", e))),
+        };
+        servo.handle_events(vec![WindowEvent::NewBrowser(url, sender)]);
+        let handle = match receiver.recv() {
+            Ok(id) => ServoHandle::new(id),
+            Err(e) => return Err(ServoError::EngineStartup(format!("{:?}", e))),
+        };
+        self.browsers.insert(handle.id, handle.target.clone());
+        Ok(handle)
+    }
+
+    /// Frees the GL resources of the targets of closed browsers
+    fn close_browsers(&mut self, alive: &HashSet<TopLevelBrowsingContextId>) {
+        let closed = self.browsers
+            .keys()
+            .filter(|id| !alive.contains(id))
+            .cloned()
+            .collect::<Vec<_>>();
+        for id in closed {
+            if let Some(target) = self.browsers.remove(&id) {
+                if let (Some(servo), Some(window)) = (self.servo.as_mut(), self.window.as_ref()) {
+                    if self.active == Some(id) {
+                        window.clear_active();
+                        self.active = None;
+                    }
+                    servo.handle_events(vec![WindowEvent::CloseBrowser(id)]);
+                    target.release(window.gl.deref());
+                }
+            }
+        }
+    }
+
+    fn setup_target(
+        &self,
+        id: TopLevelBrowsingContextId,
+        texture: &Texture,
+    ) -> Result<(), ServoError> {
+        match (self.window.as_ref(), self.browsers.get(&id)) {
+            (Some(window), Some(target)) => target.setup_framebuffer(window.gl.deref(), texture),
+            (Some(_), None) => Err(ServoError::BrowserClosed),
+            (None, _) => Err(ServoError::EngineStartup("Servo is not running".into())),
+        }
+    }
+
+    /// Makes the target of the browser the one Servo composites into
+    fn handle_events(&mut self, id: TopLevelBrowsingContextId, events: Vec<BrowserEvent>) {
+        let (servo, window, target) = match (
+            self.servo.as_mut(),
+            self.window.as_ref(),
+            self.browsers.get(&id),
+        ) {
+            (Some(servo), Some(window), Some(target)) => (servo, window, target),
+            _ => return,
+        };
+        window.set_active(target);
+        self.active = Some(id);
+        let events = events
+            .into_iter()
+            .map(|event| event.into_window_event(id))
+            .collect();
+        servo.handle_events(events);
+    }
+
+    fn setup_offscreen_target(&self, id: TopLevelBrowsingContextId) -> Result<(), ServoError> {
+        match (self.window.as_ref(), self.browsers.get(&id)) {
+            (Some(window), Some(target)) => target.setup_offscreen(window.gl.deref()),
+            (Some(_), None) => Err(ServoError::BrowserClosed),
+            (None, _) => Err(ServoError::EngineStartup("Servo is not running".into())),
+        }
+    }
+
+    fn capture(&self, id: TopLevelBrowsingContextId) -> Result<ServoImage, ServoError> {
+        match (self.window.as_ref(), self.browsers.get(&id)) {
+            (Some(window), Some(target)) => {
+                let (width, height, data) = target.read_pixels(window.gl.deref())?;
+                Ok(ServoImage {
+                    width: width,
+                    height: height,
+                    data: data,
+                })
+            }
+            (Some(_), None) => Err(ServoError::BrowserClosed),
+            (None, _) => Err(ServoError::EngineStartup("Servo is not running".into())),
+        }
+    }
+
+    fn pump(&mut self) {
+        if let Some(ref mut servo) = self.servo {
+            servo.handle_events(vec![]);
+        }
+    }
+
+    fn take_events(&mut self) -> Vec<(Option<TopLevelBrowsingContextId>, EmbedderMsg)> {
+        match self.servo {
+            Some(ref mut servo) => servo.get_events(),
+            None => vec![],
+        }
+    }
+
+    fn bridge(&self) -> Option<&Bridge> {
+        self.bridge.as_ref()
+    }
+
+    /// Asset URLs are served by the bridge
+    fn resolve_url(&self, assets: &ServoAssets, url: &str) -> String {
+        match self.bridge {
+            Some(ref bridge) => assets.resolve(url, bridge.port()),
+            None => url.to_string(),
+        }
+    }
+
+    fn unresolve_url(&self, assets: &ServoAssets, url: &str) -> String {
+        match self.bridge {
+            Some(ref bridge) => assets.unresolve(url, bridge.port()),
+            None => url.to_string(),
+        }
+    }
+}
+
+impl Drop for ServoEngine {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
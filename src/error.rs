@@ -0,0 +1,58 @@
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Failures of the engine or of a single browser, reported on the entity's ServoStatus and as
+/// a ServoEvent::Failed
+#[derive(Clone, Debug, PartialEq)]
+pub enum ServoError {
+    InvalidUrl(String),
+    ResourcesNotFound(PathBuf),
+    GlContext(String),
+    /// Status returned by glCheckFramebufferStatus
+    FramebufferIncomplete(u32),
+    LockPoisoned,
+    EngineStartup(String),
+    /// The page's script or layout thread panicked
+    Panic(String),
+    /// The browser was closed before a request to it was answered
+    BrowserClosed,
+    /// The page didn't finish what was waited for in time
+    Timeout,
+}
+
+impl fmt::Display for ServoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &ServoError::InvalidUrl(ref e) => write!(f, "Failed to parse URL: {}", e),
+            &ServoError::ResourcesNotFound(ref path) => {
+                write!(f, "Servo resources not found at {}", path.display())
+            }
+            &ServoError::GlContext(ref e) => write!(f, "Failed to use the GL context: {}", e),
+            &ServoError::FramebufferIncomplete(status) => {
+                write!(f, "Framebuffer incomplete, status {:#x}", status)
+            }
+            &ServoError::LockPoisoned => write!(f, "Lock poisoned"),
+            &ServoError::EngineStartup(ref e) => write!(f, "Failed to start Servo: {}", e),
+            &ServoError::Panic(ref reason) => write!(f, "Servo panicked: {}", reason),
+            &ServoError::BrowserClosed => write!(f, "Browser closed"),
+            &ServoError::Timeout => write!(f, "Timed out"),
+        }
+    }
+}
+
+impl Error for ServoError {
+    fn description(&self) -> &str {
+        match self {
+            &ServoError::InvalidUrl(_) => "invalid URL",
+            &ServoError::ResourcesNotFound(_) => "Servo resources not found",
+            &ServoError::GlContext(_) => "GL context error",
+            &ServoError::FramebufferIncomplete(_) => "framebuffer incomplete",
+            &ServoError::LockPoisoned => "lock poisoned",
+            &ServoError::EngineStartup(_) => "failed to start Servo",
+            &ServoError::Panic(_) => "Servo panicked",
+            &ServoError::BrowserClosed => "browser closed",
+            &ServoError::Timeout => "timed out",
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use amethyst::ecs::Entity;
+
+use super::ServoError;
+
+/// Page lifecycle events, published on the EventChannel<ServoEvent> resource
+#[derive(Clone, Debug, PartialEq)]
+pub enum ServoEvent {
+    LoadStart(Entity),
+    LoadComplete(Entity),
+    /// The page stopped working, such as when Servo panicked. Its ServoStatus holds the
+    /// error.
+    LoadError(Entity, String),
+    TitleChanged(Entity, Option<String>),
+    UrlChanged(Entity, String),
+    HistoryChanged(Entity),
+    Failed(Entity, ServoError),
+}
+
+impl ServoEvent {
+    pub fn entity(&self) -> Entity {
+        match self {
+            &ServoEvent::LoadStart(entity)
+            | &ServoEvent::LoadComplete(entity)
+            | &ServoEvent::LoadError(entity, _)
+            | &ServoEvent::TitleChanged(entity, _)
+            | &ServoEvent::UrlChanged(entity, _)
+            | &ServoEvent::HistoryChanged(entity)
+            | &ServoEvent::Failed(entity, _) => entity,
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+use amethyst::ecs::{Entity, ReadStorage, System};
+use amethyst::prelude::World;
+use amethyst::shred::{Fetch, FetchMut};
+use amethyst::shrev::{EventChannel, ReaderId};
+use amethyst::winit::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+use libservo::msg::constellation_msg::Key;
+
+use super::ServoHandle;
+use super::input::translate_key;
+
+/// Decides which ServoHandle entity receives keyboard input
+pub struct ServoFocus {
+    pub entity: Option<Entity>,
+    /// Clicking on a page focuses it, clicking outside of every page clears the focus
+    pub click_to_focus: bool,
+    // Whether the focused entity has a ServoHandle, kept up to date by the systems
+    pub(crate) handled: bool,
+    forwarded: Vec<(Key, VirtualKeyCode)>,
+    consumed: HashSet<VirtualKeyCode>,
+}
+
+impl Default for ServoFocus {
+    fn default() -> Self {
+        Self {
+            entity: None,
+            click_to_focus: true,
+            handled: false,
+            forwarded: vec![],
+            consumed: HashSet::new(),
+        }
+    }
+}
+
+impl ServoFocus {
+    pub fn focus(&mut self, entity: Entity) {
+        if self.entity != Some(entity) {
+            self.clear_keys();
+            self.handled = false;
+        }
+        self.entity = Some(entity);
+    }
+
+    pub fn blur(&mut self) {
+        self.clear_keys();
+        self.entity = None;
+        self.handled = false;
+    }
+
+    /// Whether a page takes the keyboard. Focusing an entity without a browser, because it
+    /// was deleted or its browser failed to start, doesn't take it.
+    pub fn has_keyboard(&self) -> bool {
+        self.entity.is_some() && self.handled
+    }
+
+    /// Whether the browser holds on to `key`. A forwarded key counts as consumed until Servo
+    /// hands it back as unhandled, so game bindings should skip it while this returns true.
+    /// Keys are marked by the ServoFocusSystem, game systems checking this have to run after it.
+    pub fn consumed(&self, key: VirtualKeyCode) -> bool {
+        self.consumed.contains(&key)
+    }
+
+    pub(crate) fn forward_key(&mut self, key: Key, code: VirtualKeyCode, pressed: bool) {
+        if pressed {
+            self.forwarded.push((key, code));
+            self.consumed.insert(code);
+        } else {
+            self.forwarded.retain(|&(_, c)| c != code);
+            self.consumed.remove(&code);
+        }
+    }
+
+    pub(crate) fn unhandled_key(&mut self, key: Key) {
+        let consumed = &mut self.consumed;
+        self.forwarded.retain(|&(k, code)| {
+            if k == key {
+                consumed.remove(&code);
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    fn clear_keys(&mut self) {
+        self.forwarded.clear();
+        self.consumed.clear();
+    }
+}
+
+/// Marks keys pressed while a page has the keyboard as consumed in the frame they are pressed.
+/// The ServoUiSystem that forwards them is thread local and only runs after the game's
+/// systems, so this one runs in the dispatcher. The ServoUiBundle adds it as "servo_focus",
+/// systems reading key bindings should depend on it.
+pub struct ServoFocusSystem {
+    reader_id: ReaderId<Event>,
+}
+
+impl ServoFocusSystem {
+    pub fn new(world: &mut World) -> Self {
+        Self {
+            reader_id: world
+                .write_resource::<EventChannel<Event>>()
+                .register_reader(),
+        }
+    }
+}
+
+impl<'a> System<'a> for ServoFocusSystem {
+    type SystemData = (
+        ReadStorage<'a, ServoHandle>,
+        Fetch<'a, EventChannel<Event>>,
+        FetchMut<'a, ServoFocus>,
+    );
+
+    fn run(&mut self, (handles, events, mut focus): Self::SystemData) {
+        focus.handled = focus.entity.map_or(false, |entity| handles.get(entity).is_some());
+        for event in events.read(&mut self.reader_id) {
+            if let &Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state,
+                                virtual_keycode: Some(code),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } = event
+            {
+                match state {
+                    // Keys Servo has no name for are never forwarded
+                    ElementState::Pressed => {
+                        if focus.has_keyboard() && translate_key(code).is_some() {
+                            focus.consumed.insert(code);
+                        }
+                    }
+                    ElementState::Released => {
+                        focus.consumed.remove(&code);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,93 +1,201 @@
-use libservo::{gl, Servo};
-use glutin::{GlContext, GlWindow};
-use libservo::servo_config::resource_files::set_resources_path;
-use libservo::servo_config::opts;
-use libservo::ipc_channel::ipc;
 use libservo::servo_url::ServoUrl;
-use libservo::msg::constellation_msg::TopLevelBrowsingContextId;
-use libservo::compositing::windowing::WindowEvent;
+use libservo::msg::constellation_msg::{Key, KeyModifiers, KeyState, TopLevelBrowsingContextId,
+                                       TraversalDirection};
+use libservo::compositing::windowing::{MouseWindowEvent, WindowEvent};
+use libservo::euclid::TypedPoint2D;
+use libservo::script_traits::TouchEventType;
+use libservo::style_traits::DevicePixel;
+use libservo::webrender_api::ScrollLocation;
 
-use std::env;
-use std::sync::{Arc, Mutex};
-use std::rc::Rc;
-use amethyst::winit::EventsLoopProxy;
 use amethyst::ecs::{Component, VecStorage};
+use amethyst::renderer::TextureHandle;
 
-use super::ServoWindow;
+use super::{ServoError, ServoTarget};
+use super::servo_url::Navigation;
+use super::script::{JsResult, JsValue, Scripts};
+use super::capture::{CaptureSlot, ServoCapture};
 
+/// Events queued on a ServoHandle. Servo's WindowEvent can carry IPC senders, which aren't
+/// Sync, so components only hold this subset and the engine converts it.
+pub enum BrowserEvent {
+    LoadUrl(ServoUrl),
+    Navigation(TraversalDirection),
+    Reload,
+    Mouse(MouseWindowEvent),
+    MouseMove(TypedPoint2D<f32, DevicePixel>),
+    Scroll(ScrollLocation, TypedPoint2D<i32, DevicePixel>, TouchEventType),
+    Key(Option<char>, Key, KeyState, KeyModifiers),
+    Visibility(bool),
+    /// Makes the browser the one Servo composites, sent by the ServoUiSystem before the
+    /// other events of a browser that isn't selected
+    Select,
+    /// Lays the page out at the size and HiDPI factor of its target
+    Resize((u32, u32), f32),
+    /// Multiplies the page zoom of the selected browser
+    Zoom(f32),
+    ResetZoom,
+    /// Multiplies the pinch zoom of the selected browser
+    PinchZoom(f32),
+}
+
+impl BrowserEvent {
+    pub fn into_window_event(self, id: TopLevelBrowsingContextId) -> WindowEvent {
+        match self {
+            BrowserEvent::LoadUrl(url) => WindowEvent::LoadUrl(id, url),
+            BrowserEvent::Navigation(direction) => WindowEvent::Navigation(id, direction),
+            BrowserEvent::Reload => WindowEvent::Reload(id),
+            BrowserEvent::Mouse(event) => WindowEvent::MouseWindowEventClass(event),
+            BrowserEvent::MouseMove(position) => WindowEvent::MouseWindowMoveEventClass(position),
+            BrowserEvent::Scroll(location, position, phase) => {
+                WindowEvent::Scroll(location, position, phase)
+            }
+            BrowserEvent::Key(ch, key, state, modifiers) => {
+                WindowEvent::KeyEvent(ch, key, state, modifiers)
+            }
+            BrowserEvent::Visibility(visible) => WindowEvent::ChangeBrowserVisibility(id, visible),
+            BrowserEvent::Select => WindowEvent::SelectBrowser(id),
+            // Servo reads the size from the window, which shows the selected target
+            BrowserEvent::Resize(..) => WindowEvent::Resize,
+            BrowserEvent::Zoom(magnification) => WindowEvent::Zoom(magnification),
+            BrowserEvent::ResetZoom => WindowEvent::ResetZoom,
+            BrowserEvent::PinchZoom(magnification) => WindowEvent::PinchZoom(magnification),
+        }
+    }
+}
+
+/// A browser living in the ServoEngine of the ServoUiSystem, events are queued here and
+/// handed to the engine by the system. Only ids and thread safe state are kept here, Servo
+/// itself never leaves the thread of the system.
 pub struct ServoHandle {
-    pub servo: Servo<ServoWindow>,
-    pub window: Rc<ServoWindow>,
     pub id: TopLevelBrowsingContextId,
+    pub target: ServoTarget,
+    pub events: Vec<BrowserEvent>,
+    pub(crate) history: Vec<String>,
+    pub(crate) history_index: usize,
+    pub(crate) scripts: Scripts,
+    // Visibility last reported to Servo
+    pub(crate) visible: bool,
+    // Servo reported something about the page, or its target changed, since it was last
+    // composited
+    pub(crate) pending: bool,
+    // Setting up the target failed with the texture of the Material, or without a Material
+    pub(crate) target_failed: bool,
+    pub(crate) failed_texture: Option<TextureHandle>,
+    pub(crate) captures: Vec<CaptureSlot>,
 }
 
-/// FIXME: YOU'RE GOING TO KILL SOMEONE
-unsafe impl Sync for ServoHandle {}
-unsafe impl Send for ServoHandle {}
-
 impl Component for ServoHandle {
     type Storage = VecStorage<ServoHandle>;
 }
 
 impl ServoHandle {
-    pub fn update(&mut self) {
-        self.servo.handle_events(vec![]);
+    pub fn new(id: TopLevelBrowsingContextId) -> Self {
+        Self {
+            id: id,
+            target: ServoTarget::new(),
+            events: vec![],
+            history: vec![],
+            history_index: 0,
+            scripts: Scripts::new(),
+            visible: true,
+            pending: true,
+            target_failed: false,
+            failed_texture: None,
+            captures: vec![],
+        }
     }
 
-    pub fn navigate(&mut self, url: &str) -> Result<(), String> {
+    pub fn send_events(&mut self, events: Vec<BrowserEvent>) {
+        self.events.extend(events);
+    }
+
+    pub fn navigate(&mut self, url: &str) -> Result<(), ServoError> {
         match ServoUrl::parse(url) {
             Ok(url) => {
-                self.servo
-                    .handle_events(vec![WindowEvent::LoadUrl(self.id, url)]);
+                self.events.push(BrowserEvent::LoadUrl(url));
                 Ok(())
             }
-            Err(e) => Err(format!("Failed to parse URL: {}", e)),
+            Err(e) => Err(ServoError::InvalidUrl(format!("{}", e))),
         }
     }
-}
 
-impl ServoHandle {
-    pub fn start_servo(window: &Arc<GlWindow>, events: &EventsLoopProxy, url: &str) -> ServoHandle {
-        // Fetch gl context
-        let gl = unsafe {
-            window
-                .context()
-                .make_current()
-                .expect("Failed to make current");
-            gl::GlFns::load_with(|s| window.context().get_proc_address(s) as *const _)
-        };
-
-        // Create renderer
-        let renderer = Rc::new(ServoWindow {
-            gl: gl,
-            waker: events.clone(),
-            window: window.clone(),
-            dimensions: Arc::new(Mutex::new((1024, 1024))),
-            target_texture: Arc::new(Mutex::new(None)),
-            buffers: Arc::new(Mutex::new(None)),
+    pub fn back(&mut self) {
+        self.events
+            .push(BrowserEvent::Navigation(TraversalDirection::Back(1)));
+    }
+
+    pub fn forward(&mut self) {
+        self.events
+            .push(BrowserEvent::Navigation(TraversalDirection::Forward(1)));
+    }
+
+    pub fn reload(&mut self) {
+        self.events.push(BrowserEvent::Reload);
+    }
+
+    /// Servo can't abort a load it has already started, so this only drops navigations that
+    /// haven't been handed to the engine yet
+    pub fn stop(&mut self) {
+        self.events.retain(|event| match event {
+            &BrowserEvent::LoadUrl(_) | &BrowserEvent::Navigation(_) | &BrowserEvent::Reload => {
+                false
+            }
+            _ => true,
         });
+    }
 
-        // Get resources
-        let path = env::current_dir().unwrap().join("resources");
-        set_resources_path(Some(path.to_str().unwrap().to_string()));
-        let mut opts = opts::default_opts();
-        opts.dump_display_list = true;
-        opts::set_defaults(opts);
-
-        // Start servo
-        let mut servo = Servo::new(renderer.clone());
-
-        // Launch servo
-        let url = ServoUrl::parse(&url).unwrap();
-        let (sender, receiver) = ipc::channel().unwrap();
-        servo.handle_events(vec![WindowEvent::NewBrowser(url, sender)]);
-        let id = receiver.recv().unwrap();
-        servo.handle_events(vec![WindowEvent::SelectBrowser(id)]);
-
-        ServoHandle {
-            window: renderer.clone(),
-            servo: servo,
-            id: id,
+    pub fn apply(&mut self, navigation: Navigation) {
+        match navigation {
+            Navigation::Back => self.back(),
+            Navigation::Forward => self.forward(),
+            Navigation::Reload => self.reload(),
+            Navigation::Stop => self.stop(),
         }
     }
+
+    /// Runs the script in the page once it has loaded, the result is the value of the last
+    /// expression or what the promise it returns resolves to
+    pub fn evaluate_js(&mut self, script: &str) -> JsResult {
+        self.scripts.evaluate(script)
+    }
+
+    /// Dispatches an "amethyst-message" event on the page window, the data is in its detail
+    pub fn post_message(&mut self, data: JsValue) {
+        self.scripts.post_message(data);
+    }
+
+    /// Composites the page and reads it back, the next time the ServoUiSystem runs
+    pub fn capture(&mut self) -> ServoCapture {
+        let (capture, slot) = ServoCapture::new();
+        self.captures.push(slot);
+        self.pending = true;
+        capture
+    }
+
+    /// Hidden by its ServoVisibility or out of view, Servo throttles hidden pages
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// The page isn't animating and has nothing queued, it is only composited again once
+    /// Servo reports something about it or new events are sent
+    pub fn is_idle(&self) -> bool {
+        !self.pending && self.events.is_empty() && !self.target.is_animating()
+    }
+
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    pub fn current_url(&self) -> Option<&str> {
+        self.history.get(self.history_index).map(|url| url.as_str())
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        self.history_index > 0
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        self.history_index + 1 < self.history.len()
+    }
 }
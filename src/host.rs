@@ -0,0 +1,77 @@
+use std::sync::Arc;
+use glutin::{GlContext, GlWindow};
+use amethyst::winit::EventsLoopProxy;
+use libservo::compositing::compositor_thread::EventLoopWaker;
+
+/// GL context Servo composites with and the window around it, the game window or an
+/// offscreen context
+pub trait ServoHost {
+    fn make_current(&self) -> Result<(), String>;
+    fn get_proc_address(&self, name: &str) -> *const ();
+    fn hidpi_factor(&self) -> f32;
+    /// Monitor size, inner size and position of the window, None without a window
+    fn window_metrics(&self) -> Option<((u32, u32), (u32, u32), (i32, i32))>;
+    fn create_event_loop_waker(&self) -> Box<EventLoopWaker>;
+}
+
+/// Hosts Servo in the game window, wakes the winit event loop when Servo has work to do
+pub struct WindowHost {
+    window: Arc<GlWindow>,
+    proxy: EventsLoopProxy,
+}
+
+impl WindowHost {
+    pub fn new(window: Arc<GlWindow>, proxy: EventsLoopProxy) -> Self {
+        Self {
+            window: window,
+            proxy: proxy,
+        }
+    }
+}
+
+impl ServoHost for WindowHost {
+    fn make_current(&self) -> Result<(), String> {
+        unsafe { self.window.context().make_current() }.map_err(|e| format!("{}", e))
+    }
+
+    fn get_proc_address(&self, name: &str) -> *const () {
+        self.window.context().get_proc_address(name)
+    }
+
+    fn hidpi_factor(&self) -> f32 {
+        self.window.hidpi_factor()
+    }
+
+    fn window_metrics(&self) -> Option<((u32, u32), (u32, u32), (i32, i32))> {
+        let screen = self.window.get_current_monitor().get_dimensions();
+        let size = match self.window.get_inner_size() {
+            Some(size) => size,
+            None => return None,
+        };
+        let position = self.window.get_position().unwrap_or((0, 0));
+        Some((screen, size, position))
+    }
+
+    fn create_event_loop_waker(&self) -> Box<EventLoopWaker> {
+        Box::new(WinitEventLoopWaker {
+            waker: self.proxy.clone(),
+        })
+    }
+}
+
+struct WinitEventLoopWaker {
+    waker: EventsLoopProxy,
+}
+
+impl EventLoopWaker for WinitEventLoopWaker {
+    fn clone(&self) -> Box<EventLoopWaker + Send> {
+        Box::new(Self {
+            waker: self.waker.clone(),
+        })
+    }
+    fn wake(&self) {
+        if let Err(e) = self.waker.wakeup() {
+            eprintln!("Failed to wake the event loop: {:?}", e);
+        }
+    }
+}
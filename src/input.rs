@@ -0,0 +1,320 @@
+use amethyst::winit::{ElementState, KeyboardInput, ModifiersState, MouseButton as WinitMouseButton,
+                      MouseScrollDelta, VirtualKeyCode, WindowEvent as WinitWindowEvent};
+use libservo::compositing::windowing::MouseWindowEvent;
+use libservo::euclid::{TypedPoint2D, TypedVector2D};
+use libservo::msg::constellation_msg::{Key, KeyModifiers, KeyState};
+use libservo::script_traits::{MouseButton, TouchEventType};
+use libservo::webrender_api::ScrollLocation;
+use super::handle::BrowserEvent;
+
+// Same values the servo glutin port uses
+const LINE_HEIGHT: f32 = 38.0;
+const CLICK_DISTANCE: f64 = 10.0;
+
+/// Pointer input in window space, before it is mapped onto a page
+pub enum PointerInput {
+    Moved,
+    Pressed(MouseButton),
+    Released(MouseButton, bool),
+    Scrolled(f32, f32),
+}
+
+pub struct MouseState {
+    pub cursor: (f64, f64),
+    down: Option<(f64, f64)>,
+}
+
+impl MouseState {
+    pub fn new() -> Self {
+        Self {
+            cursor: (0., 0.),
+            down: None,
+        }
+    }
+
+    pub fn update(&mut self, event: &WinitWindowEvent) -> Option<PointerInput> {
+        match event {
+            &WinitWindowEvent::CursorMoved { position, .. } => {
+                self.cursor = position;
+                Some(PointerInput::Moved)
+            }
+            &WinitWindowEvent::MouseInput { state, button, .. } => {
+                let button = match translate_button(button) {
+                    Some(button) => button,
+                    None => return None,
+                };
+                match state {
+                    ElementState::Pressed => {
+                        self.down = Some(self.cursor);
+                        Some(PointerInput::Pressed(button))
+                    }
+                    ElementState::Released => {
+                        let click = match self.down.take() {
+                            Some((x, y)) => {
+                                let (dx, dy) = (self.cursor.0 - x, self.cursor.1 - y);
+                                (dx * dx + dy * dy).sqrt() < CLICK_DISTANCE
+                            }
+                            None => false,
+                        };
+                        Some(PointerInput::Released(button, click))
+                    }
+                }
+            }
+            &WinitWindowEvent::MouseWheel { delta, .. } => {
+                let (dx, dy) = match delta {
+                    MouseScrollDelta::LineDelta(dx, dy) => (dx * LINE_HEIGHT, dy * LINE_HEIGHT),
+                    MouseScrollDelta::PixelDelta(dx, dy) => (dx, dy),
+                };
+                // Scroll along the dominant axis only
+                if dy.abs() >= dx.abs() {
+                    Some(PointerInput::Scrolled(0., dy))
+                } else {
+                    Some(PointerInput::Scrolled(dx, 0.))
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+fn translate_button(button: WinitMouseButton) -> Option<MouseButton> {
+    match button {
+        WinitMouseButton::Left => Some(MouseButton::Left),
+        WinitMouseButton::Middle => Some(MouseButton::Middle),
+        WinitMouseButton::Right => Some(MouseButton::Right),
+        WinitMouseButton::Other(_) => None,
+    }
+}
+
+/// Builds the servo events for `input` happening at `point` in page pixels
+pub fn pointer_events(input: &PointerInput, point: (f32, f32)) -> Vec<BrowserEvent> {
+    let position = TypedPoint2D::new(point.0, point.1);
+    match input {
+        &PointerInput::Moved => vec![BrowserEvent::MouseMove(position)],
+        &PointerInput::Pressed(button) => vec![
+            BrowserEvent::Mouse(MouseWindowEvent::MouseDown(button, position)),
+        ],
+        &PointerInput::Released(button, click) => {
+            let mut events = vec![BrowserEvent::Mouse(MouseWindowEvent::MouseUp(button, position))];
+            if click {
+                events.push(BrowserEvent::Mouse(MouseWindowEvent::Click(button, position)));
+            }
+            events
+        }
+        &PointerInput::Scrolled(dx, dy) => vec![
+            BrowserEvent::Scroll(
+                ScrollLocation::Delta(TypedVector2D::new(dx, dy)),
+                TypedPoint2D::new(point.0 as i32, point.1 as i32),
+                TouchEventType::Move,
+            ),
+        ],
+    }
+}
+
+/// Tracks the keyboard so printable keys can be paired with the character winit reports
+/// for them before they are forwarded
+pub struct KeyboardState {
+    modifiers: KeyModifiers,
+    pending: Option<(Key, VirtualKeyCode)>,
+}
+
+impl KeyboardState {
+    pub fn new() -> Self {
+        Self {
+            modifiers: KeyModifiers::empty(),
+            pending: None,
+        }
+    }
+
+    pub fn update(&mut self, event: &WinitWindowEvent) -> Vec<(VirtualKeyCode, BrowserEvent)> {
+        let mut events = vec![];
+        match event {
+            &WinitWindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(code),
+                        modifiers,
+                        ..
+                    },
+                ..
+            } => {
+                self.modifiers = translate_modifiers(modifiers);
+                let key = match translate_key(code) {
+                    Some(key) => key,
+                    None => return events,
+                };
+                // A printable key that never produced a character still has to be delivered
+                if let Some((key, code)) = self.pending.take() {
+                    events.push((code, self.key_event(None, key, KeyState::Pressed)));
+                }
+                match state {
+                    ElementState::Pressed if is_printable(code) => {
+                        self.pending = Some((key, code));
+                    }
+                    ElementState::Pressed => {
+                        events.push((code, self.key_event(None, key, KeyState::Pressed)));
+                    }
+                    ElementState::Released => {
+                        events.push((code, self.key_event(None, key, KeyState::Released)));
+                    }
+                }
+            }
+            &WinitWindowEvent::ReceivedCharacter(ch) => {
+                if let Some((key, code)) = self.pending.take() {
+                    let ch = if ch.is_control() { None } else { Some(ch) };
+                    events.push((code, self.key_event(ch, key, KeyState::Pressed)));
+                }
+            }
+            _ => {}
+        }
+        events
+    }
+
+    fn key_event(&self, ch: Option<char>, key: Key, state: KeyState) -> BrowserEvent {
+        BrowserEvent::Key(ch, key, state, self.modifiers)
+    }
+}
+
+pub fn translate_modifiers(modifiers: ModifiersState) -> KeyModifiers {
+    let mut result = KeyModifiers::empty();
+    if modifiers.shift {
+        result.insert(KeyModifiers::SHIFT);
+    }
+    if modifiers.ctrl {
+        result.insert(KeyModifiers::CONTROL);
+    }
+    if modifiers.alt {
+        result.insert(KeyModifiers::ALT);
+    }
+    if modifiers.logo {
+        result.insert(KeyModifiers::SUPER);
+    }
+    result
+}
+
+fn is_printable(code: VirtualKeyCode) -> bool {
+    use amethyst::winit::VirtualKeyCode::*;
+    match code {
+        Escape | F1 | F2 | F3 | F4 | F5 | F6 | F7 | F8 | F9 | F10 | F11 | F12 | F13 | F14
+        | F15 | Snapshot | Scroll | Pause | Insert | Home | Delete | End | PageDown | PageUp
+        | Left | Up | Right | Down | Back | LAlt | LControl | LMenu | LShift | LWin | Mail
+        | MediaSelect | MediaStop | Mute | MyComputer | NavigateForward | NavigateBackward
+        | NextTrack | NoConvert | PlayPause | Power | PrevTrack | RAlt | RControl | RMenu
+        | RShift | RWin | Sleep | Stop | VolumeDown | VolumeUp | Wake | WebBack | WebFavorites
+        | WebForward | WebHome | WebRefresh | WebSearch | WebStop | Capital | Apps | Tab
+        | Return | NumpadEnter | Numlock => false,
+        _ => true,
+    }
+}
+
+pub fn translate_key(code: VirtualKeyCode) -> Option<Key> {
+    use amethyst::winit::VirtualKeyCode as V;
+    Some(match code {
+        V::A => Key::A,
+        V::B => Key::B,
+        V::C => Key::C,
+        V::D => Key::D,
+        V::E => Key::E,
+        V::F => Key::F,
+        V::G => Key::G,
+        V::H => Key::H,
+        V::I => Key::I,
+        V::J => Key::J,
+        V::K => Key::K,
+        V::L => Key::L,
+        V::M => Key::M,
+        V::N => Key::N,
+        V::O => Key::O,
+        V::P => Key::P,
+        V::Q => Key::Q,
+        V::R => Key::R,
+        V::S => Key::S,
+        V::T => Key::T,
+        V::U => Key::U,
+        V::V => Key::V,
+        V::W => Key::W,
+        V::X => Key::X,
+        V::Y => Key::Y,
+        V::Z => Key::Z,
+
+        V::Numpad0 => Key::Kp0,
+        V::Numpad1 => Key::Kp1,
+        V::Numpad2 => Key::Kp2,
+        V::Numpad3 => Key::Kp3,
+        V::Numpad4 => Key::Kp4,
+        V::Numpad5 => Key::Kp5,
+        V::Numpad6 => Key::Kp6,
+        V::Numpad7 => Key::Kp7,
+        V::Numpad8 => Key::Kp8,
+        V::Numpad9 => Key::Kp9,
+
+        V::Key0 => Key::Num0,
+        V::Key1 => Key::Num1,
+        V::Key2 => Key::Num2,
+        V::Key3 => Key::Num3,
+        V::Key4 => Key::Num4,
+        V::Key5 => Key::Num5,
+        V::Key6 => Key::Num6,
+        V::Key7 => Key::Num7,
+        V::Key8 => Key::Num8,
+        V::Key9 => Key::Num9,
+
+        V::Return => Key::Enter,
+        V::Space => Key::Space,
+        V::Escape => Key::Escape,
+        V::Equals => Key::Equal,
+        V::Minus => Key::Minus,
+        V::Back => Key::Backspace,
+        V::PageDown => Key::PageDown,
+        V::PageUp => Key::PageUp,
+
+        V::Insert => Key::Insert,
+        V::Home => Key::Home,
+        V::Delete => Key::Delete,
+        V::End => Key::End,
+
+        V::Left => Key::Left,
+        V::Up => Key::Up,
+        V::Right => Key::Right,
+        V::Down => Key::Down,
+
+        V::LShift => Key::LeftShift,
+        V::LControl => Key::LeftControl,
+        V::LAlt => Key::LeftAlt,
+        V::LWin => Key::LeftSuper,
+        V::RShift => Key::RightShift,
+        V::RControl => Key::RightControl,
+        V::RAlt => Key::RightAlt,
+        V::RWin => Key::RightSuper,
+
+        V::Apostrophe => Key::Apostrophe,
+        V::Backslash => Key::Backslash,
+        V::Comma => Key::Comma,
+        V::Grave => Key::GraveAccent,
+        V::LBracket => Key::LeftBracket,
+        V::Period => Key::Period,
+        V::RBracket => Key::RightBracket,
+        V::Semicolon => Key::Semicolon,
+        V::Slash => Key::Slash,
+        V::Tab => Key::Tab,
+        V::Subtract => Key::Minus,
+
+        V::F1 => Key::F1,
+        V::F2 => Key::F2,
+        V::F3 => Key::F3,
+        V::F4 => Key::F4,
+        V::F5 => Key::F5,
+        V::F6 => Key::F6,
+        V::F7 => Key::F7,
+        V::F8 => Key::F8,
+        V::F9 => Key::F9,
+        V::F10 => Key::F10,
+        V::F11 => Key::F11,
+        V::F12 => Key::F12,
+
+        V::NavigateBackward => Key::NavigateBackward,
+        V::NavigateForward => Key::NavigateForward,
+        _ => return None,
+    })
+}
@@ -4,22 +4,75 @@ extern crate gfx_core;
 extern crate gfx_device_gl;
 extern crate glutin;
 extern crate hibitset;
+extern crate png;
+#[cfg(feature = "testing")]
+extern crate rayon;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
 extern crate servo as libservo;
 
 pub mod bundle;
 pub mod system;
 pub mod servo_size;
 pub mod handle;
+pub mod engine;
+pub mod backend;
 pub mod servo_url;
 pub mod servo_blit;
+pub mod focus;
+pub mod event;
+pub mod error;
+pub mod status;
+pub mod visibility;
+pub mod background;
+pub mod zoom;
+pub mod capture;
+pub mod host;
+pub mod message;
+pub mod picking;
+pub mod script;
+pub mod assets;
+pub mod config;
+pub mod prefab;
+pub mod bridge;
 mod window;
+mod target;
+mod input;
+mod compositor;
+mod watcher;
 pub mod pass;
+pub mod mesh_pass;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 pub use self::bundle::ServoUiBundle;
 use self::window::ServoWindow;
+use self::target::ServoTarget;
 use self::handle::ServoHandle;
+pub use self::engine::ServoEngine;
+pub use self::backend::ServoBackend;
 use self::system::ServoUiSystem;
 pub use self::pass::ServoPass;
+pub use self::mesh_pass::{ServoMesh, ServoMeshPass};
 pub use self::servo_size::ServoSize;
-pub use self::servo_url::ServoUrl;
-pub use self::servo_blit::ServoBlit;
+pub use self::servo_url::{Navigation, ServoUrl};
+pub use self::servo_blit::{Anchor, ServoBlit};
+pub use self::focus::{ServoFocus, ServoFocusSystem};
+pub use self::event::ServoEvent;
+pub use self::error::ServoError;
+pub use self::status::ServoStatus;
+pub use self::visibility::ServoVisibility;
+pub use self::background::ServoBackground;
+pub use self::zoom::ServoZoom;
+pub use self::capture::{ServoCapture, ServoImage};
+pub use self::host::{ServoHost, WindowHost};
+pub use self::message::ServoMessage;
+pub use self::picking::ServoRaycastTarget;
+pub use self::assets::ServoAssets;
+pub use self::bridge::Bridge;
+pub use self::config::{ServoConfig, ServoPref};
+pub use self::prefab::{InputMode, ServoPlane, ServoPrefab};
+pub use self::script::{JsError, JsResult, JsValue};
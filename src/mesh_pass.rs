@@ -0,0 +1,152 @@
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+use std::mem;
+use amethyst::assets::AssetStorage;
+use amethyst::core::cgmath::{Matrix4, SquareMatrix};
+use amethyst::core::transform::GlobalTransform;
+use amethyst::ecs::{Component, Entities, Fetch, Join, ReadStorage, VecStorage};
+use amethyst::renderer::pipe::pass::{Pass, PassData};
+use amethyst::renderer::{Camera, DepthMode, Effect, Encoder, Factory, Material, Mesh,
+                         MeshHandle, NewEffect, Position, Query, TexCoord, Texture};
+use amethyst::renderer::error::Result as RendererResult;
+use gfx_core::pso::ElemStride;
+use gfx_core::state::ColorMask;
+use super::ServoVisibility;
+use super::pass::PREMULTIPLIED_ALPHA;
+
+/// Mesh of a page drawn by the ServoMeshPass. Used instead of a MeshHandle, which DrawPbm and
+/// DrawFlat would also draw without blending.
+#[derive(Clone)]
+pub struct ServoMesh(pub MeshHandle);
+
+impl Component for ServoMesh {
+    type Storage = VecStorage<ServoMesh>;
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct VertexArgs {
+    proj: [[f32; 4]; 4],
+    view: [[f32; 4]; 4],
+    model: [[f32; 4]; 4],
+}
+
+/// Draws pages on meshes without lighting, blending their premultiplied colors over the scene
+/// so transparent backgrounds show what is behind the page. It goes after the passes drawing
+/// the opaque scene.
+pub struct ServoMeshPass<V> {
+    _pd: PhantomData<V>,
+}
+
+impl<V> ServoMeshPass<V>
+where
+    V: Query<(Position, TexCoord)>,
+{
+    pub fn new() -> Self {
+        Self { _pd: PhantomData }
+    }
+}
+
+type ServoMeshPassData<'a> = (
+    Entities<'a>,
+    ReadStorage<'a, ServoMesh>,
+    ReadStorage<'a, Material>,
+    ReadStorage<'a, GlobalTransform>,
+    ReadStorage<'a, ServoVisibility>,
+    ReadStorage<'a, Camera>,
+    Fetch<'a, AssetStorage<Mesh>>,
+    Fetch<'a, AssetStorage<Texture>>,
+);
+
+impl<'a, V> PassData<'a> for ServoMeshPass<V>
+where
+    V: Query<(Position, TexCoord)>,
+{
+    type Data = ServoMeshPassData<'a>;
+}
+
+const VERT_SRC: &[u8] = include_bytes!("shaders/mesh.vert");
+const FRAG_SRC: &[u8] = include_bytes!("shaders/shader.frag");
+
+impl<V> Pass for ServoMeshPass<V>
+where
+    V: Query<(Position, TexCoord)>,
+{
+    fn compile(&mut self, effect: NewEffect) -> RendererResult<Effect> {
+        effect
+            .simple(VERT_SRC, FRAG_SRC)
+            .with_raw_constant_buffer("VertexArgs", mem::size_of::<VertexArgs>(), 1)
+            .with_raw_vertex_buffer(V::QUERIED_ATTRIBUTES, V::size() as ElemStride, 0)
+            .with_texture("albedo")
+            .with_blended_output(
+                "color",
+                ColorMask::all(),
+                PREMULTIPLIED_ALPHA,
+                Some(DepthMode::LessEqualTest),
+            )
+            .build()
+    }
+
+    fn apply<'a, 'b: 'a>(
+        &'a mut self,
+        encoder: &mut Encoder,
+        effect: &mut Effect,
+        _factory: Factory,
+        (
+            entities,
+            meshes,
+            materials,
+            globals,
+            visibilities,
+            cameras,
+            mesh_storage,
+            tex_storage,
+        ): ServoMeshPassData,
+    ) {
+        let matrices = (&cameras, &globals).join().next().and_then(|(camera, transform)| {
+            Matrix4::from(transform.0)
+                .invert()
+                .map(|view| (camera.proj, view))
+        });
+        let (proj, view) = match matrices {
+            Some(matrices) => matrices,
+            None => return,
+        };
+        // Back to front, so nearer pages blend over the ones behind them
+        let mut sorted = (&*entities, &meshes, &materials, &globals)
+            .join()
+            .filter(|&(entity, _, _, _)| visibilities.get(entity).map_or(true, |v| v.visible))
+            .map(|(_, mesh, material, global)| {
+                let depth = (view * Matrix4::from(global.0)).w.z;
+                (depth, mesh, material, global)
+            })
+            .collect::<Vec<_>>();
+        sorted.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        for (_, mesh, material, global) in sorted {
+            let mesh = match mesh_storage.get(&mesh.0) {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+            let (vbuf, image) = match (
+                mesh.buffer(V::QUERIED_ATTRIBUTES),
+                tex_storage.get(&material.albedo),
+            ) {
+                (Some(vbuf), Some(image)) => (vbuf, image),
+                _ => continue,
+            };
+            let vertex_args = VertexArgs {
+                proj: proj.into(),
+                view: view.into(),
+                model: global.0,
+            };
+            effect.update_constant_buffer("VertexArgs", &vertex_args, encoder);
+            effect.data.vertex_bufs.push(vbuf.clone());
+            effect.data.textures.push(image.view().clone());
+            effect.data.samplers.push(image.sampler().clone());
+            effect.draw(mesh.slice(), encoder);
+            effect.data.vertex_bufs.clear();
+            effect.data.textures.clear();
+            effect.data.samplers.clear();
+        }
+    }
+}
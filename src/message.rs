@@ -0,0 +1,14 @@
+use amethyst::ecs::Entity;
+
+use super::script::JsValue;
+
+/// Data a page passed to window.amethyst.postMessage, published on the
+/// EventChannel<ServoMessage> resource
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServoMessage {
+    pub entity: Entity,
+    /// URL of the page the browser was showing when the message arrived, as the game sees it.
+    /// Check it before trusting data from browsers that can navigate to other sites.
+    pub url: String,
+    pub data: JsValue,
+}
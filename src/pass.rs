@@ -1,13 +1,36 @@
+use std::cmp::Ordering;
+use std::mem;
 use amethyst::renderer::pipe::pass::{Pass, PassData};
 use amethyst::renderer::{DepthMode, Effect, Encoder, Factory, Material, Mesh, NewEffect, PosTex,
-                         Texture, VertexFormat};
+                         ScreenDimensions, Texture, VertexFormat};
 use amethyst::renderer::error::Result as RendererResult;
-use amethyst::ecs::{Fetch, Join, ReadStorage};
+use amethyst::ecs::{Entities, Fetch, Join, ReadStorage};
 use amethyst::assets::AssetStorage;
 use gfx_core::pso::ElemStride;
 use gfx_core::state::ColorMask;
-use draw_state::preset::blend;
-use super::ServoBlit;
+use draw_state::state::{Blend, BlendChannel, BlendValue, Equation, Factor};
+use super::{ServoBlit, ServoSize, ServoVisibility};
+
+/// Blending for page textures, Servo renders premultiplied alpha. Passes drawing pages on
+/// meshes need it for transparent backgrounds to composite correctly.
+pub const PREMULTIPLIED_ALPHA: Blend = Blend {
+    color: BlendChannel {
+        equation: Equation::Add,
+        source: Factor::One,
+        destination: Factor::OneMinus(BlendValue::SourceAlpha),
+    },
+    alpha: BlendChannel {
+        equation: Equation::Add,
+        source: Factor::One,
+        destination: Factor::OneMinus(BlendValue::SourceAlpha),
+    },
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct VertexArgs {
+    rect: [f32; 4],
+}
 
 pub struct ServoPass {
     mesh: Option<Mesh>,
@@ -20,9 +43,13 @@ impl ServoPass {
 }
 
 type ServoPassData<'a> = (
+    Entities<'a>,
     ReadStorage<'a, ServoBlit>,
+    ReadStorage<'a, ServoSize>,
+    ReadStorage<'a, ServoVisibility>,
     ReadStorage<'a, Material>,
     Fetch<'a, AssetStorage<Texture>>,
+    Fetch<'a, ScreenDimensions>,
 );
 
 impl<'a> PassData<'a> for ServoPass {
@@ -63,9 +90,10 @@ impl Pass for ServoPass {
         self.mesh = Some(Mesh::build(data).build(&mut effect.factory)?);
         effect
             .simple(VERT_SRC, FRAG_SRC)
+            .with_raw_constant_buffer("VertexArgs", mem::size_of::<VertexArgs>(), 1)
             .with_raw_vertex_buffer(PosTex::ATTRIBUTES, PosTex::size() as ElemStride, 0)
             .with_texture("albedo")
-            .with_blended_output("color", ColorMask::all(), blend::ALPHA, None)
+            .with_blended_output("color", ColorMask::all(), PREMULTIPLIED_ALPHA, None)
             .build()
     }
     fn apply<'a, 'b: 'a>(
@@ -73,7 +101,7 @@ impl Pass for ServoPass {
         encoder: &mut Encoder,
         effect: &mut Effect,
         _factory: Factory,
-        (blits, materials, tex_storage): ServoPassData,
+        (entities, blits, sizes, visibilities, materials, tex_storage, screen): ServoPassData,
     ) {
         let mesh = self.mesh.as_ref().unwrap();
 
@@ -81,8 +109,29 @@ impl Pass for ServoPass {
             Some(vbuf) => effect.data.vertex_bufs.push(vbuf.clone()),
             None => return,
         };
-        for (material, _) in (&materials, &blits).join() {
+        let window = (screen.width() as u32, screen.height() as u32);
+        // Back to front, so overlays with a higher z blend over the ones below them
+        let mut sorted = (&*entities, &materials, &blits).join().collect::<Vec<_>>();
+        sorted.sort_by(|a, b| a.2.z.partial_cmp(&b.2.z).unwrap_or(Ordering::Equal));
+        for (entity, material, blit) in sorted {
+            if !visibilities.get(entity).map_or(true, |v| v.visible) {
+                continue;
+            }
+            let page = sizes
+                .get(entity)
+                .map(|size| size.pixel_size())
+                .unwrap_or(window);
+            let (x, y, width, height) = blit.screen_rect(window, page);
+            let vertex_args = VertexArgs {
+                rect: [
+                    x / window.0 as f32 * 2. - 1.,
+                    1. - (y + height) / window.1 as f32 * 2.,
+                    width / window.0 as f32 * 2.,
+                    height / window.1 as f32 * 2.,
+                ],
+            };
             if let Some(image) = tex_storage.get(&material.albedo) {
+                effect.update_constant_buffer("VertexArgs", &vertex_args, encoder);
                 effect.data.textures.push(image.view().clone());
                 effect.data.samplers.push(image.sampler().clone());
                 effect.draw(mesh.slice(), encoder);
@@ -0,0 +1,194 @@
+use amethyst::core::cgmath::{InnerSpace, Matrix4, Point3, SquareMatrix, Transform, Vector2,
+                             Vector3, Vector4};
+use amethyst::core::transform::GlobalTransform;
+use amethyst::ecs::{Component, VecStorage};
+use amethyst::renderer::{Camera, PosNormTangTex, PosTex};
+
+/// CPU side copy of the mesh a page is rendered onto, used to pick the page with the cursor.
+/// Meshes only live on the GPU, so this has to be built from the same vertices as the mesh.
+pub struct ServoRaycastTarget {
+    pub triangles: Vec<[([f32; 3], [f32; 2]); 3]>,
+    /// Receives the cursor, targets without input are still used to cull the page
+    pub input: bool,
+}
+
+impl Component for ServoRaycastTarget {
+    type Storage = VecStorage<ServoRaycastTarget>;
+}
+
+impl ServoRaycastTarget {
+    /// Builds triangles from a non-indexed triangle list
+    pub fn new<I: IntoIterator<Item = ([f32; 3], [f32; 2])>>(vertices: I) -> Self {
+        let vertices = vertices.into_iter().collect::<Vec<_>>();
+        Self {
+            triangles: vertices
+                .chunks(3)
+                .filter(|c| c.len() == 3)
+                .map(|c| [c[0], c[1], c[2]])
+                .collect(),
+            input: true,
+        }
+    }
+
+    /// Returns the world space distance along the ray and the texture coordinate of the
+    /// closest hit
+    pub fn intersect(&self, ray: &Ray, transform: &GlobalTransform) -> Option<(f32, [f32; 2])> {
+        let model = Matrix4::from(transform.0);
+        let local = match model.invert() {
+            Some(inverse) => ray.transform(&inverse),
+            None => return None,
+        };
+        let mut closest: Option<(f32, [f32; 2])> = None;
+        for triangle in &self.triangles {
+            if let Some((t, uv)) = intersect_triangle(&local, triangle) {
+                let hit = model.transform_point(local.origin + local.direction * t);
+                let distance = (hit - ray.origin).magnitude();
+                match closest {
+                    Some((d, _)) if d <= distance => {}
+                    _ => closest = Some((distance, uv)),
+                }
+            }
+        }
+        closest
+    }
+
+    /// Whether any part of the mesh may be inside the view frustum of the camera, the mesh
+    /// is culled only when all of its vertices are outside the same clip plane
+    pub fn in_view(
+        &self,
+        camera: &Camera,
+        camera_transform: &GlobalTransform,
+        transform: &GlobalTransform,
+    ) -> bool {
+        let view = match Matrix4::from(camera_transform.0).invert() {
+            Some(view) => view,
+            None => return true,
+        };
+        let clip = camera.proj * view * Matrix4::from(transform.0);
+        let mut outside = 0b11_1111;
+        for triangle in &self.triangles {
+            for &(position, _) in triangle.iter() {
+                let p = clip * Vector4::new(position[0], position[1], position[2], 1.);
+                let mut code = 0;
+                if p.x < -p.w {
+                    code |= 0b00_0001;
+                }
+                if p.x > p.w {
+                    code |= 0b00_0010;
+                }
+                if p.y < -p.w {
+                    code |= 0b00_0100;
+                }
+                if p.y > p.w {
+                    code |= 0b00_1000;
+                }
+                if p.z < -p.w {
+                    code |= 0b01_0000;
+                }
+                if p.z > p.w {
+                    code |= 0b10_0000;
+                }
+                outside &= code;
+                if outside == 0 {
+                    return true;
+                }
+            }
+        }
+        self.triangles.is_empty()
+    }
+}
+
+impl<'a> From<&'a [PosNormTangTex]> for ServoRaycastTarget {
+    fn from(vertices: &[PosNormTangTex]) -> Self {
+        Self::new(vertices.iter().map(|v| (v.position, v.tex_coord)))
+    }
+}
+
+impl<'a> From<&'a [PosTex]> for ServoRaycastTarget {
+    fn from(vertices: &[PosTex]) -> Self {
+        Self::new(vertices.iter().map(|v| (v.position, v.tex_coord)))
+    }
+}
+
+pub struct Ray {
+    pub origin: Point3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    /// Casts a ray from the camera through the cursor, given in window pixels
+    pub fn from_camera(
+        cursor: (f64, f64),
+        window: (u32, u32),
+        camera: &Camera,
+        transform: &GlobalTransform,
+    ) -> Option<Ray> {
+        if window.0 == 0 || window.1 == 0 {
+            return None;
+        }
+        let x = (2. * cursor.0 / window.0 as f64 - 1.) as f32;
+        let y = (1. - 2. * cursor.1 / window.1 as f64) as f32;
+        let view = match Matrix4::from(transform.0).invert() {
+            Some(view) => view,
+            None => return None,
+        };
+        let inverse = match (camera.proj * view).invert() {
+            Some(inverse) => inverse,
+            None => return None,
+        };
+        let near = inverse * Vector4::new(x, y, -1., 1.);
+        let far = inverse * Vector4::new(x, y, 1., 1.);
+        let near = Point3::from_homogeneous(near);
+        let far = Point3::from_homogeneous(far);
+        Some(Ray {
+            origin: near,
+            direction: (far - near).normalize(),
+        })
+    }
+
+    fn transform(&self, matrix: &Matrix4<f32>) -> Ray {
+        Ray {
+            origin: matrix.transform_point(self.origin),
+            direction: matrix.transform_vector(self.direction),
+        }
+    }
+}
+
+/// Converts a texture coordinate into the device pixels of a page, texture space has its
+/// origin at the bottom left while pages have theirs at the top left
+pub fn uv_to_page(uv: [f32; 2], page: (u32, u32)) -> (f32, f32) {
+    (uv[0] * page.0 as f32, (1. - uv[1]) * page.1 as f32)
+}
+
+// Möller–Trumbore, returns the ray parameter and the interpolated texture coordinate
+fn intersect_triangle(ray: &Ray, triangle: &[([f32; 3], [f32; 2]); 3]) -> Option<(f32, [f32; 2])> {
+    const EPSILON: f32 = 1e-6;
+    let p0 = Point3::from(triangle[0].0);
+    let p1 = Point3::from(triangle[1].0);
+    let p2 = Point3::from(triangle[2].0);
+    let edge1 = p1 - p0;
+    let edge2 = p2 - p0;
+    let h = ray.direction.cross(edge2);
+    let det = edge1.dot(h);
+    if det.abs() < EPSILON {
+        return None;
+    }
+    let f = 1. / det;
+    let s = ray.origin - p0;
+    let u = f * s.dot(h);
+    if u < 0. || u > 1. {
+        return None;
+    }
+    let q = s.cross(edge1);
+    let v = f * ray.direction.dot(q);
+    if v < 0. || u + v > 1. {
+        return None;
+    }
+    let t = f * edge2.dot(q);
+    if t < EPSILON {
+        return None;
+    }
+    let uv = Vector2::from(triangle[0].1) * (1. - u - v) + Vector2::from(triangle[1].1) * u
+        + Vector2::from(triangle[2].1) * v;
+    Some((t, uv.into()))
+}
@@ -0,0 +1,200 @@
+use amethyst::assets::{AssetStorage, Loader};
+use amethyst::core::cgmath::{Deg, Euler, Matrix4, Vector3};
+use amethyst::core::transform::GlobalTransform;
+use amethyst::ecs::Entity;
+use amethyst::prelude::World;
+use amethyst::renderer::{MaterialDefaults, Mesh, MeshHandle, PosNormTangTex};
+
+use super::{ServoBackground, ServoBlit, ServoFocus, ServoMesh, ServoRaycastTarget, ServoSize,
+            ServoUrl, ServoVisibility, ServoZoom};
+
+/// A browser panel described as data, loadable from RON with Config::load like ServoConfig.
+/// A scene with several panels can be loaded as a `Vec<ServoPrefab>`. The amethyst version
+/// this builds on has no prefab system, so panels are added with `create_entity`, or with
+/// `create_entity_with_mesh` for pages drawn on a mesh other than the plane.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServoPrefab {
+    /// Page to load, asset paths use the scheme of the ServoAssets resource
+    pub url: String,
+    /// Layout size in CSS pixels
+    pub size: (u32, u32),
+    /// Follow the size of the game window instead of `size`
+    pub fit_window: bool,
+    /// Device pixels per CSS pixel, None uses the HiDPI factor of the game window
+    pub device_pixel_ratio: Option<f32>,
+    /// Draws the page as an overlay
+    pub blit: Option<ServoBlit>,
+    /// Draws the page on a plane in the world
+    pub plane: Option<ServoPlane>,
+    pub visible: bool,
+    /// Color the page is composited over, opaque white by default
+    pub background: Option<ServoBackground>,
+    pub zoom: Option<ServoZoom>,
+    /// Gives the page the keyboard once it is created
+    pub focus: bool,
+    /// Whether the cursor reaches the page, this overrides the input of the blit
+    pub input: InputMode,
+}
+
+/// Input a page receives from the cursor. The keyboard follows the ServoFocus either way.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InputMode {
+    /// Clicks and moves over the blit or plane are sent to the page
+    Pointer,
+    /// The page only displays, the cursor passes through to whatever is below it
+    None,
+}
+
+impl Default for ServoPrefab {
+    fn default() -> Self {
+        Self {
+            url: "about:blank".into(),
+            size: (1024, 768),
+            fit_window: false,
+            device_pixel_ratio: None,
+            blit: None,
+            plane: None,
+            visible: true,
+            background: None,
+            zoom: None,
+            focus: false,
+            input: InputMode::Pointer,
+        }
+    }
+}
+
+/// A quad in the world the page is drawn on, two units wide and high before scaling and
+/// facing +z before rotating. Other meshes are passed to `create_entity_with_mesh`, they
+/// can't be named here as picking needs their vertices, which loaded meshes keep on the GPU.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServoPlane {
+    pub translation: [f32; 3],
+    /// Euler angles in degrees
+    pub rotation: [f32; 3],
+    pub scale: [f32; 2],
+    /// Draw the quad with the ServoMeshPass instead of DrawPbm, so a transparent background
+    /// shows the scene behind the page
+    pub transparent: bool,
+}
+
+impl Default for ServoPlane {
+    fn default() -> Self {
+        Self {
+            translation: [0., 0., 0.],
+            rotation: [0., 0., 0.],
+            scale: [1., 1.],
+            transparent: false,
+        }
+    }
+}
+
+impl ServoPlane {
+    pub fn transform(&self) -> GlobalTransform {
+        let r = self.rotation;
+        let matrix = Matrix4::from_translation(Vector3::from(self.translation))
+            * Matrix4::from(Euler::new(Deg(r[0]), Deg(r[1]), Deg(r[2])))
+            * Matrix4::from_nonuniform_scale(self.scale[0], self.scale[1], 1.);
+        GlobalTransform(matrix.into())
+    }
+
+    pub fn vertices() -> Vec<PosNormTangTex> {
+        let corners: [([f32; 2], [f32; 2]); 6] = [
+            ([-1., -1.], [0., 0.]),
+            ([1., -1.], [1., 0.]),
+            ([1., 1.], [1., 1.]),
+            ([-1., -1.], [0., 0.]),
+            ([1., 1.], [1., 1.]),
+            ([-1., 1.], [0., 1.]),
+        ];
+        corners
+            .iter()
+            .map(|&(position, tex_coord)| PosNormTangTex {
+                position: [position[0], position[1], 0.],
+                normal: [0., 0., 1.],
+                tangent: [1., 0., 0.],
+                tex_coord: tex_coord,
+            })
+            .collect()
+    }
+}
+
+impl ServoPrefab {
+    /// Creates the browser entity. Pages drawn as a blit or on a plane need the RenderBundle,
+    /// pages with neither are only rendered offscreen.
+    pub fn create_entity(&self, world: &mut World) -> Entity {
+        let vertices = self.plane.as_ref().map(|_| ServoPlane::vertices());
+        self.build(world, vertices)
+    }
+
+    /// Creates the browser entity with the page drawn on a mesh built from the vertices
+    /// instead of the quad, placed by the plane or at the origin without one
+    pub fn create_entity_with_mesh(
+        &self,
+        world: &mut World,
+        vertices: Vec<PosNormTangTex>,
+    ) -> Entity {
+        self.build(world, Some(vertices))
+    }
+
+    fn build(&self, world: &mut World, vertices: Option<Vec<PosNormTangTex>>) -> Entity {
+        let mut size = if self.fit_window {
+            ServoSize::fit_window()
+        } else {
+            ServoSize::from(self.size)
+        };
+        size.device_pixel_ratio = self.device_pixel_ratio;
+        let visibility = ServoVisibility {
+            visible: self.visible,
+        };
+        let material = if self.blit.is_some() || vertices.is_some() {
+            Some(world.read_resource::<MaterialDefaults>().0.clone())
+        } else {
+            None
+        };
+        let input = self.input == InputMode::Pointer;
+        let mesh = vertices.map(|vertices| {
+            let plane = self.plane.clone().unwrap_or_default();
+            let mut target = ServoRaycastTarget::from(&vertices[..]);
+            target.input = input;
+            let loader = world.read_resource::<Loader>();
+            let meshes = world.read_resource::<AssetStorage<Mesh>>();
+            let mesh: MeshHandle = loader.load_from_data(vertices.into(), (), &meshes);
+            (mesh, target, plane.transform(), plane.transparent)
+        });
+        let mut builder = world
+            .create_entity()
+            .with(ServoUrl::from(self.url.as_str()))
+            .with(size)
+            .with(visibility);
+        if let Some(background) = self.background {
+            builder = builder.with(background);
+        }
+        if let Some(zoom) = self.zoom {
+            builder = builder.with(zoom);
+        }
+        if let Some(ref blit) = self.blit {
+            builder = builder.with(ServoBlit {
+                input: input,
+                ..blit.clone()
+            });
+        }
+        if let Some((mesh, target, transform, transparent)) = mesh {
+            builder = if transparent {
+                builder.with(ServoMesh(mesh))
+            } else {
+                builder.with(mesh)
+            };
+            builder = builder.with(target).with(transform);
+        }
+        if let Some(material) = material {
+            builder = builder.with(material);
+        }
+        let entity = builder.build();
+        if self.focus {
+            world.write_resource::<ServoFocus>().focus(entity);
+        }
+        entity
+    }
+}
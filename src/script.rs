@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use serde_json::{self, Value};
+
+/// Value returned by a script, promises are resolved before they are returned
+pub type JsValue = Value;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsError {
+    /// The script threw, or returned a value that can't be serialized
+    Exception(String),
+    /// The page navigated away before the script finished
+    Navigated,
+    /// The browser was closed, the bridge could not be started or the page loaded without
+    /// connecting to it
+    Unavailable,
+}
+
+type Callback = Box<FnMut(Result<JsValue, JsError>) + Send>;
+
+// Shared between the JsResult and the browser until the result is delivered, a Sender
+// would make the ServoHandle holding it !Sync
+type Slot = Arc<Mutex<(Option<Result<JsValue, JsError>>, Option<Callback>)>>;
+
+/// Result of ServoHandle::evaluate_js, delivered once the page has run the script
+pub struct JsResult {
+    slot: Slot,
+}
+
+impl JsResult {
+    /// Returns None while the script is still running, then the result every time
+    pub fn try_get(&self) -> Option<Result<JsValue, JsError>> {
+        match self.slot.lock() {
+            Ok(slot) => match slot.0 {
+                Some(ref result) => Some(result.clone()),
+                // The browser dropped the script without answering
+                None if Arc::strong_count(&self.slot) == 1 => Some(Err(JsError::Unavailable)),
+                None => None,
+            },
+            Err(_) => Some(Err(JsError::Unavailable)),
+        }
+    }
+
+    /// Calls `callback` with the result once the page has run the script, on the thread of
+    /// the ServoUiSystem. It is called right away if the result is already in.
+    pub fn then<F>(self, callback: F)
+    where
+        F: FnOnce(Result<JsValue, JsError>) + Send + 'static,
+    {
+        let mut callback = Some(callback);
+        let mut callback: Callback = Box::new(move |result| {
+            if let Some(callback) = callback.take() {
+                callback(result);
+            }
+        });
+        let result = match self.slot.lock() {
+            Ok(mut slot) => match slot.0.clone() {
+                Some(result) => result,
+                None => {
+                    slot.1 = Some(callback);
+                    return;
+                }
+            },
+            Err(_) => Err(JsError::Unavailable),
+        };
+        callback(result);
+    }
+}
+
+fn resolve(slot: Slot, result: Result<JsValue, JsError>) {
+    let callback = match slot.lock() {
+        Ok(mut slot) => {
+            slot.0 = Some(result.clone());
+            slot.1.take()
+        }
+        Err(_) => None,
+    };
+    // Called once the lock is released
+    if let Some(mut callback) = callback {
+        callback(result);
+    }
+}
+
+/// Commands of a single browser, waiting to be sent to the page or for their result
+pub struct Scripts {
+    next_id: u64,
+    pub(crate) token: Option<String>,
+    queued: Vec<Value>,
+    waiting: HashMap<u64, Slot>,
+    // Whether a page has loaded, scripts evaluated before the first one wait for it
+    loaded: bool,
+    // The page loaded without connecting to the bridge, scripts would never reach it
+    unreachable: bool,
+}
+
+impl Scripts {
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            token: None,
+            queued: vec![],
+            waiting: HashMap::new(),
+            loaded: false,
+            unreachable: false,
+        }
+    }
+
+    pub fn evaluate(&mut self, script: &str) -> JsResult {
+        let slot = Arc::new(Mutex::new((None, None)));
+        if self.unreachable {
+            resolve(slot.clone(), Err(JsError::Unavailable));
+            return JsResult { slot: slot };
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.queued.push(json!({"type": "eval", "id": id, "script": script}));
+        self.waiting.insert(id, slot.clone());
+        JsResult { slot: slot }
+    }
+
+    pub fn post_message(&mut self, data: Value) {
+        if self.unreachable {
+            return;
+        }
+        self.queued.push(json!({"type": "message", "data": data}));
+    }
+
+    /// Makes the page fetch the stylesheets again, without reloading it
+    pub fn reload_stylesheets(&mut self, urls: Vec<String>) {
+        if self.unreachable {
+            return;
+        }
+        self.queued.push(json!({"type": "reload-css", "urls": urls}));
+    }
+
+    /// Takes the commands that can be sent to the connected page
+    pub fn drain(&mut self) -> Vec<String> {
+        if self.token.is_none() {
+            return vec![];
+        }
+        self.queued.drain(..).map(|command| command.to_string()).collect()
+    }
+
+    pub fn connect(&mut self, token: String) {
+        self.token = Some(token);
+        self.unreachable = false;
+    }
+
+    /// The page is going away, scripts it was given or that were meant for it will never
+    /// report back. Scripts evaluated before the first page loaded wait for the next one.
+    pub fn disconnect(&mut self) {
+        self.token = None;
+        self.unreachable = false;
+        if !self.loaded {
+            return;
+        }
+        self.queued.clear();
+        for (_, slot) in self.waiting.drain() {
+            resolve(slot, Err(JsError::Navigated));
+        }
+    }
+
+    /// The page finished loading, if it didn't connect by now it never will
+    pub fn loaded(&mut self) {
+        self.loaded = true;
+        if self.token.is_none() {
+            self.unreachable = true;
+            self.abandon();
+        }
+    }
+
+    /// The page can't be reached, fails every script
+    pub fn abandon(&mut self) {
+        self.queued.clear();
+        for (_, slot) in self.waiting.drain() {
+            resolve(slot, Err(JsError::Unavailable));
+        }
+    }
+
+    /// Handles a message the page posted, returns it back if it isn't a script result
+    pub fn receive(&mut self, message: Value) -> Option<Value> {
+        if message["type"] != "result" {
+            return Some(message);
+        }
+        let id = match message["id"].as_u64() {
+            Some(id) => id,
+            None => return None,
+        };
+        if let Some(slot) = self.waiting.remove(&id) {
+            let result = if message["ok"] == true {
+                Ok(message["value"].clone())
+            } else {
+                Err(JsError::Exception(match message["value"] {
+                    Value::String(ref error) => error.clone(),
+                    ref other => serde_json::to_string(other).unwrap_or_default(),
+                }))
+            };
+            resolve(slot, result);
+        }
+        None
+    }
+}
+
+impl Drop for Scripts {
+    // The browser is gone, scripts still waiting fail rather than never calling back
+    fn drop(&mut self) {
+        self.abandon();
+    }
+}
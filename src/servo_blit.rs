@@ -1,5 +1,96 @@
 use amethyst::ecs::{Component, VecStorage};
-pub struct ServoBlit {}
+
+/// Window edge or corner a blit is positioned relative to
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Anchor {
+    TopLeft,
+    TopMiddle,
+    TopRight,
+    MiddleLeft,
+    Middle,
+    MiddleRight,
+    BottomLeft,
+    BottomMiddle,
+    BottomRight,
+}
+
+impl Anchor {
+    fn norm(&self) -> (f32, f32) {
+        match *self {
+            Anchor::TopLeft => (0., 0.),
+            Anchor::TopMiddle => (0.5, 0.),
+            Anchor::TopRight => (1., 0.),
+            Anchor::MiddleLeft => (0., 0.5),
+            Anchor::Middle => (0.5, 0.5),
+            Anchor::MiddleRight => (1., 0.5),
+            Anchor::BottomLeft => (0., 1.),
+            Anchor::BottomMiddle => (0.5, 1.),
+            Anchor::BottomRight => (1., 1.),
+        }
+    }
+}
+
+/// Draws the page as a 2D overlay with the ServoPass
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServoBlit {
+    /// Offset from the anchor in window pixels, positive values go right and down
+    pub position: (f32, f32),
+    /// Size on screen in window pixels, None draws the page one window pixel per device pixel
+    pub size: Option<(f32, f32)>,
+    pub anchor: Anchor,
+    /// Blits with a higher z are drawn on top and receive input first
+    pub z: f32,
+    /// Receives the cursor, blits without input let it through to the pages below
+    pub input: bool,
+}
+
+impl Default for ServoBlit {
+    fn default() -> Self {
+        Self {
+            position: (0., 0.),
+            size: None,
+            anchor: Anchor::TopLeft,
+            z: 0.,
+            input: true,
+        }
+    }
+}
+
+impl ServoBlit {
+    /// Rectangle covered on screen as (x, y, width, height) in window pixels, measured from
+    /// the top left of the window
+    pub fn screen_rect(&self, window: (u32, u32), page: (u32, u32)) -> (f32, f32, f32, f32) {
+        let (width, height) = self.size.unwrap_or((page.0 as f32, page.1 as f32));
+        let (ax, ay) = self.anchor.norm();
+        (
+            ax * (window.0 as f32 - width) + self.position.0,
+            ay * (window.1 as f32 - height) + self.position.1,
+            width,
+            height,
+        )
+    }
+
+    /// Maps a cursor position in window pixels onto the device pixels of the page, returns
+    /// None if the cursor is not over the page
+    pub fn page_position(
+        &self,
+        cursor: (f64, f64),
+        window: (u32, u32),
+        page: (u32, u32),
+    ) -> Option<(f32, f32)> {
+        let (x, y, width, height) = self.screen_rect(window, page);
+        if width <= 0. || height <= 0. {
+            return None;
+        }
+        let x = (cursor.0 as f32 - x) / width;
+        let y = (cursor.1 as f32 - y) / height;
+        if x < 0. || x >= 1. || y < 0. || y >= 1. {
+            return None;
+        }
+        Some((x * page.0 as f32, y * page.1 as f32))
+    }
+}
 
 impl Component for ServoBlit {
     type Storage = VecStorage<ServoBlit>;
@@ -1,8 +1,17 @@
 pub use amethyst::ecs::{Component, VecStorage};
+
+/// Layout size of the page in CSS pixels, the render target is this size multiplied by the
+/// device pixel ratio
 pub struct ServoSize {
     pub width: u32,
     pub height: u32,
     pub dirty: bool,
+    /// Follow the size and HiDPI factor of the game window, for full screen overlays
+    pub fit_window: bool,
+    /// Device pixels per CSS pixel, None uses the HiDPI factor of the game window
+    pub device_pixel_ratio: Option<f32>,
+    // Ratio in use, resolved by the ServoUiSystem
+    pub(crate) hidpi_factor: f32,
 }
 
 impl Component for ServoSize {
@@ -11,11 +20,7 @@ impl Component for ServoSize {
 
 impl From<(u32, u32)> for ServoSize {
     fn from(dim: (u32, u32)) -> Self {
-        Self {
-            width: dim.0,
-            height: dim.1,
-            dirty: true,
-        }
+        Self::new(dim.0, dim.1)
     }
 }
 
@@ -25,6 +30,34 @@ impl ServoSize {
             width: width,
             height: height,
             dirty: true,
+            fit_window: false,
+            device_pixel_ratio: None,
+            hidpi_factor: 1.,
+        }
+    }
+
+    /// A size that tracks the game window, the real size is filled in by the ServoUiSystem
+    pub fn fit_window() -> Self {
+        Self {
+            fit_window: true,
+            ..Self::new(1, 1)
         }
     }
+
+    pub fn with_device_pixel_ratio(mut self, ratio: f32) -> Self {
+        self.device_pixel_ratio = Some(ratio);
+        self
+    }
+
+    pub fn hidpi_factor(&self) -> f32 {
+        self.hidpi_factor
+    }
+
+    /// Size of the render target in device pixels
+    pub fn pixel_size(&self) -> (u32, u32) {
+        (
+            (self.width as f32 * self.hidpi_factor).round() as u32,
+            (self.height as f32 * self.hidpi_factor).round() as u32,
+        )
+    }
 }
@@ -1,8 +1,19 @@
 use amethyst::ecs::{Component, VecStorage};
 use std::convert::From;
+
+/// Navigation requests applied by the ServoUiSystem on the next frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Navigation {
+    Back,
+    Forward,
+    Reload,
+    Stop,
+}
+
 pub struct ServoUrl {
     pub dirty: bool,
     pub url: String,
+    pub navigation: Vec<Navigation>,
 }
 
 impl ServoUrl {
@@ -10,6 +21,22 @@ impl ServoUrl {
         self.dirty = true;
         self.url = url.clone();
     }
+
+    pub fn back(&mut self) {
+        self.navigation.push(Navigation::Back);
+    }
+
+    pub fn forward(&mut self) {
+        self.navigation.push(Navigation::Forward);
+    }
+
+    pub fn reload(&mut self) {
+        self.navigation.push(Navigation::Reload);
+    }
+
+    pub fn stop(&mut self) {
+        self.navigation.push(Navigation::Stop);
+    }
 }
 
 impl From<String> for ServoUrl {
@@ -17,6 +44,7 @@ impl From<String> for ServoUrl {
         Self {
             dirty: true,
             url: string,
+            navigation: vec![],
         }
     }
 }
@@ -26,6 +54,7 @@ impl<'a> From<&'a str> for ServoUrl {
         Self {
             dirty: true,
             url: string.to_string(),
+            navigation: vec![],
         }
     }
 }
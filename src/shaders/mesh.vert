@@ -0,0 +1,21 @@
+#version 150 core
+
+layout (std140) uniform VertexArgs {
+    mat4 proj;
+    mat4 view;
+    mat4 model;
+};
+
+in vec3 in_position;
+in vec2 tex_coord;
+
+out VertexData {
+  vec4 position;
+  vec2 tex_coord;
+} vertex;
+
+void main() {
+    vertex.position = proj * view * model * vec4(in_position, 1);
+    vertex.tex_coord = tex_coord;
+    gl_Position = vertex.position;
+}
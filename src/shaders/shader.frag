@@ -10,5 +10,5 @@ in VertexData {
 out vec4 color;
 
 void main(){
-    color = vec4(1., 1., 1., 1.);
+    color = texture(albedo, vertex.tex_coord);
 }
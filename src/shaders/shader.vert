@@ -1,5 +1,10 @@
 #version 150 core
 
+layout (std140) uniform VertexArgs {
+    // Normalized device coordinates of the bottom left corner, then width and height
+    vec4 rect;
+};
+
 in vec3 in_position;
 in vec2 tex_coord;
 out vec2 tex_coord_out;
@@ -10,7 +15,7 @@ out VertexData {
 } vertex;
 
 void main() {
-    vertex.position = vec4(in_position.xy - vec2(1., 1.) * vec2(2., 2.), 0, 1);
+    vertex.position = vec4(rect.xy + in_position.xy * rect.zw, 0, 1);
     vertex.tex_coord = tex_coord;
     gl_Position = vertex.position;
 }
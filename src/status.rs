@@ -0,0 +1,26 @@
+use amethyst::ecs::{Component, VecStorage};
+
+use super::ServoError;
+
+/// State of a browser entity, inserted by the ServoUiSystem
+#[derive(Clone, Debug, PartialEq)]
+pub enum ServoStatus {
+    Loading,
+    Loaded,
+    /// The browser could not be created or stopped working, setting ServoUrl::goto retries
+    /// a browser that failed to start
+    Failed(ServoError),
+}
+
+impl Component for ServoStatus {
+    type Storage = VecStorage<ServoStatus>;
+}
+
+impl ServoStatus {
+    pub fn error(&self) -> Option<&ServoError> {
+        match self {
+            &ServoStatus::Failed(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
@@ -1,44 +1,122 @@
 extern crate genmesh;
 
-use std::ops::Deref;
-use amethyst::ecs::{Entities, Join, RunningTime, System, WriteStorage};
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use std::rc::Rc;
+use amethyst::ecs::{Entities, Entity, Join, ReadStorage, RunningTime, System, WriteStorage};
 use glutin::GlWindow;
 use std::sync::Arc;
 use amethyst::prelude::World;
-use super::{ServoHandle, ServoSize, ServoUrl};
-use amethyst::renderer::{Material, Texture, TextureData, TextureMetadata};
+use super::{ServoAssets, ServoBackground, ServoBlit, ServoConfig, ServoEngine, ServoEvent,
+            ServoFocus, ServoHandle, ServoError, ServoMessage, ServoRaycastTarget, ServoSize,
+            ServoStatus, ServoUrl, ServoVisibility, ServoZoom};
+use super::backend::ServoBackend;
+use super::compositor::Compositor;
+use super::handle::BrowserEvent;
+use super::host::{ServoHost, WindowHost};
+use super::input::{pointer_events, KeyboardState, MouseState, PointerInput};
+use super::picking::{uv_to_page, Ray};
+use super::bridge::BRIDGE_SCHEME;
+use super::script::JsValue;
+use super::watcher::{is_stylesheet, Watcher};
+use serde_json;
+use amethyst::core::transform::GlobalTransform;
+use amethyst::renderer::{Camera, Material, ScreenDimensions, Texture, TextureData,
+                         TextureMetadata};
 use amethyst::winit::{Event, EventsLoopProxy};
 use amethyst::shrev::{EventChannel, ReaderId};
-use amethyst::shred::Fetch;
+use amethyst::shred::{Fetch, FetchMut};
+use libservo::embedder_traits::EmbedderMsg;
+use libservo::msg::constellation_msg::KeyState;
 use amethyst::assets::{AssetStorage, Loader};
 use hibitset::BitSetNot;
 
-pub struct ServoUiSystem {
+pub struct ServoUiSystem<B: ServoBackend = ServoEngine> {
     reader_id: ReaderId<Event>,
+    // Servo isn't thread safe, so it lives in the system which only runs on the main thread
+    engine: B,
+    compositor: Compositor,
+    mouse: MouseState,
+    // Page that got the last mouse press and the last point of it under the cursor, it
+    // receives the release wherever it happens
+    pressed: Option<(Entity, (f32, f32))>,
+    keyboard: KeyboardState,
+    watcher: Option<Watcher>,
 }
 
 impl ServoUiSystem {
-    pub fn new(world: &mut World) -> Self {
+    /// Hosts Servo in the game window, the RenderBundle has to be added first
+    pub fn new(world: &mut World, config: ServoConfig) -> Self {
+        let host = WindowHost::new(
+            world.read_resource::<Arc<GlWindow>>().clone(),
+            world.read_resource::<EventsLoopProxy>().clone(),
+        );
+        Self::with_host(world, config, Rc::new(host))
+    }
+
+    /// Hosts Servo in any GL context, such as an offscreen one for tests
+    pub fn with_host(world: &mut World, config: ServoConfig, host: Rc<ServoHost>) -> Self {
+        let (hot_reload, inject_css) = (config.hot_reload, config.hot_reload_css);
+        let system = Self::with_engine(world, ServoEngine::new(host, config));
+        if hot_reload {
+            system.with_hot_reload(inject_css)
+        } else {
+            system
+        }
+    }
+}
+
+impl<B: ServoBackend> ServoUiSystem<B> {
+    /// Runs the system on another backend, such as a mock that records what it is asked
+    pub fn with_engine(world: &mut World, engine: B) -> Self {
         Self {
             reader_id: world
                 .write_resource::<EventChannel<Event>>()
                 .register_reader(),
+            engine: engine,
+            compositor: Compositor::new(),
+            mouse: MouseState::new(),
+            pressed: None,
+            keyboard: KeyboardState::new(),
+            watcher: None,
         }
     }
+
+    /// Reloads pages when their files change, stylesheets are swapped in place if
+    /// `inject_css` is set
+    pub fn with_hot_reload(mut self, inject_css: bool) -> Self {
+        self.watcher = Some(Watcher::new(inject_css));
+        self
+    }
+
+    pub fn engine(&self) -> &B {
+        &self.engine
+    }
 }
 
-impl<'a> System<'a> for ServoUiSystem {
+impl<'a, B: ServoBackend> System<'a> for ServoUiSystem<B> {
     type SystemData = (
         WriteStorage<'a, ServoHandle>,
         WriteStorage<'a, ServoUrl>,
         WriteStorage<'a, ServoSize>,
         WriteStorage<'a, Material>,
+        WriteStorage<'a, ServoStatus>,
+        WriteStorage<'a, ServoZoom>,
+        ReadStorage<'a, ServoBlit>,
+        ReadStorage<'a, ServoRaycastTarget>,
+        ReadStorage<'a, ServoVisibility>,
+        ReadStorage<'a, ServoBackground>,
+        ReadStorage<'a, Camera>,
+        ReadStorage<'a, GlobalTransform>,
         Entities<'a>,
         Fetch<'a, EventChannel<Event>>,
         Fetch<'a, AssetStorage<Texture>>,
-        Fetch<'a, Arc<GlWindow>>,
-        Fetch<'a, EventsLoopProxy>,
         Fetch<'a, Loader>,
+        Fetch<'a, ScreenDimensions>,
+        Fetch<'a, ServoAssets>,
+        FetchMut<'a, ServoFocus>,
+        FetchMut<'a, EventChannel<ServoEvent>>,
+        FetchMut<'a, EventChannel<ServoMessage>>,
     );
     fn running_time(&self) -> RunningTime {
         RunningTime::Average
@@ -51,14 +129,27 @@ impl<'a> System<'a> for ServoUiSystem {
             mut urls,
             mut sizes,
             mut materials,
+            mut statuses,
+            mut zooms,
+            blits,
+            raycast_targets,
+            visibilities,
+            backgrounds,
+            cameras,
+            globals,
             entities,
             events,
             tex_storage,
-            gl_window,
-            event_proxy,
             loader,
+            screen,
+            assets,
+            mut focus,
+            mut servo_events,
+            mut servo_messages,
         ): Self::SystemData,
     ) {
+        let engine = &mut self.engine;
+
         // INIT ROUTINE
         for (entity, url, _) in (
             &*entities,
@@ -66,67 +157,508 @@ impl<'a> System<'a> for ServoUiSystem {
             &BitSetNot(servo_handles.open().0.clone()),
         ).join()
         {
-            servo_handles.insert(
-                entity,
-                ServoHandle::start_servo(gl_window.deref(), event_proxy.deref(), &url.url),
-            );
+            // Browsers that failed to start are retried when a new URL is set
+            if !url.dirty {
+                continue;
+            }
+            url.dirty = false;
+            match engine.new_browser(&assets, &url.url) {
+                Ok(handle) => {
+                    servo_handles.insert(entity, handle);
+                    statuses.insert(entity, ServoStatus::Loading);
+                }
+                Err(e) => {
+                    eprintln!("Failed to create browser: {}", e);
+                    fail(entity, e, &mut statuses, &mut servo_events);
+                }
+            }
+        }
+
+        // RESIZE ROUTINE
+        let window_size = (screen.width() as u32, screen.height() as u32);
+        let window_hidpi = engine.hidpi_factor();
+        for (size,) in (&mut sizes,).join() {
+            let hidpi_factor = size.device_pixel_ratio.unwrap_or(window_hidpi);
+            if size.hidpi_factor != hidpi_factor {
+                size.hidpi_factor = hidpi_factor;
+                size.dirty = true;
+            }
+            if size.fit_window {
+                let css_size = (
+                    (window_size.0 as f32 / window_hidpi).round() as u32,
+                    (window_size.1 as f32 / window_hidpi).round() as u32,
+                );
+                if (size.width, size.height) != css_size {
+                    size.width = css_size.0;
+                    size.height = css_size.1;
+                    size.dirty = true;
+                }
+            }
         }
 
         // TEXTURE ROUTINE
-        for (size, servo, material) in (&mut sizes, &servo_handles, &mut materials).join() {
+        for (entity, size, servo) in (&*entities, &mut sizes, &mut servo_handles).join() {
             if size.dirty == true {
-                servo.window.set_dimensions(size.width, size.height);
+                let (width, height) = size.pixel_size();
+                servo.pending = true;
+                servo.target_failed = false;
+                servo.target.set_dimensions(width, height);
+                servo.target.set_fit_window(size.fit_window);
+                servo.target.set_hidpi_factor(size.hidpi_factor());
+                size.dirty = false;
+                if let Err(e) = servo.target.remove_target() {
+                    eprintln!("Failed to remove old target: {}", e);
+                    fail(entity, e, &mut statuses, &mut servo_events);
+                }
+                // Pages without a Material get an offscreen target in the EVENT ROUTINE
+                let material = match materials.get_mut(entity) {
+                    Some(material) => material,
+                    None => continue,
+                };
                 let texture_data = TextureData::Rgba(
                     [1., 1., 1., 0.],
                     TextureMetadata {
                         sampler: None,
                         mip_levels: Some(1),
-                        size: Some((size.width as u16, size.height as u16)),
+                        size: Some((width as u16, height as u16)),
                         dynamic: false,
                         format: None,
                         channel: None,
                     },
                 );
                 let tex_handle = loader.load_from_data(texture_data, (), &tex_storage);
-                size.dirty = false;
                 material.albedo = tex_handle;
-                if let Err(e) = servo.window.remove_target() {
-                    panic!("Failed to remove old target: {:?}", e);
-                }
             }
         }
 
         // EVENT ROUTINE
-        for (handle, material, url) in (&mut servo_handles, &mut materials, &mut urls).join() {
-            match handle.window.has_target() {
-                Ok(false) => match tex_storage.get(&material.albedo) {
-                    Some(t) => match handle.window.setup_framebuffer(t) {
-                        Ok(()) => println!("Setup framebuffer and render target"),
-                        Err(e) => {
-                            eprintln!("Failed to setup framebuffer and render target: {:?}", e)
-                        }
+        for (entity, handle, url) in (&*entities, &mut servo_handles, &mut urls).join() {
+            let background = backgrounds.get(entity).cloned().unwrap_or_default();
+            if handle.target.get_background() != background.color {
+                handle.target.set_background(background.color);
+                handle.pending = true;
+            }
+            let zoom = zooms.get(entity).map_or((1., 1.), |zoom| zoom.clamped());
+            handle.target.set_zoom(zoom);
+            let albedo = materials.get(entity).map(|material| material.albedo.clone());
+            // A target that failed is only set up again once its size or texture changes
+            let failed = handle.target_failed && handle.failed_texture == albedo;
+            if let (Ok(false), false) = (handle.target.has_target(), failed) {
+                let setup = match albedo {
+                    Some(ref albedo) => match tex_storage.get(albedo) {
+                        Some(t) => Some(engine.setup_target(handle.id, t)),
+                        None => None,
                     },
+                    None => Some(engine.setup_offscreen_target(handle.id)),
+                };
+                match setup {
+                    Some(Ok(())) => {
+                        handle.target_failed = false;
+                        handle.pending = true;
+                    }
+                    Some(Err(e)) => {
+                        eprintln!("Failed to setup framebuffer and render target: {}", e);
+                        handle.target_failed = true;
+                        handle.failed_texture = albedo;
+                        fail(entity, e, &mut statuses, &mut servo_events);
+                    }
                     None => {}
-                },
-                _ => {}
+                }
             }
             if url.dirty == true {
-                if let Err(e) = handle.navigate(&url.url) {
+                url.dirty = false;
+                if let Err(e) = handle.navigate(&engine.resolve_url(&assets, &url.url)) {
                     eprintln!("Failed navigation: {}", e);
-                } else {
-                    url.dirty = false;
+                    fail(entity, e, &mut statuses, &mut servo_events);
                 }
             }
+            for navigation in url.navigation.drain(..) {
+                handle.apply(navigation);
+            }
         }
+
+        // VISIBILITY ROUTINE
+        // Pages that are hidden, or can't be seen because they are off screen or outside the
+        // frustum of the camera, are throttled by Servo
+        let camera = (&cameras, &globals).join().next();
+        for (entity, handle) in (&*entities, &mut servo_handles).join() {
+            let mut visible = visibilities.get(entity).map_or(true, |v| v.visible);
+            if let (Some(blit), Some(size)) = (blits.get(entity), sizes.get(entity)) {
+                let (x, y, width, height) = blit.screen_rect(window_size, size.pixel_size());
+                visible &= x < window_size.0 as f32 && y < window_size.1 as f32 && x + width > 0.
+                    && y + height > 0.;
+            }
+            let mesh = (raycast_targets.get(entity), globals.get(entity), camera);
+            if let (Some(target), Some(transform), Some((camera, camera_transform))) = mesh {
+                visible &= target.in_view(camera, camera_transform, transform);
+            }
+            if handle.visible != visible {
+                handle.visible = visible;
+                handle.events.push(BrowserEvent::Visibility(visible));
+            }
+        }
+        // Servo doesn't say which browser it woke the event loop for
+        let mut awakened = false;
         for event in events.read(&mut self.reader_id) {
+            match event {
+                &Event::Awakened => awakened = true,
+                &Event::WindowEvent { ref event, .. } => {
+                    // INPUT ROUTINE
+                    if let Some(input) = self.mouse.update(event) {
+                        let mut targets = vec![];
+                        // Only the topmost overlay under the cursor receives the input
+                        let mut topmost = None;
+                        for (entity, size, blit) in (&*entities, &sizes, &blits).join() {
+                            if !blit.input || !visibilities.get(entity).map_or(true, |v| v.visible)
+                            {
+                                continue;
+                            }
+                            let page = size.pixel_size();
+                            if let Some(point) =
+                                blit.page_position(self.mouse.cursor, window_size, page)
+                            {
+                                match topmost {
+                                    Some((z, _, _)) if z > blit.z => {}
+                                    _ => topmost = Some((blit.z, entity, point)),
+                                }
+                            }
+                        }
+                        if let Some((_, entity, point)) = topmost {
+                            targets.push((entity, point));
+                        }
+                        // Pages in the world only see the cursor when no overlay covers it
+                        if targets.is_empty() {
+                            let ray = (&cameras, &globals).join().next().and_then(
+                                |(camera, transform)| {
+                                    Ray::from_camera(
+                                        self.mouse.cursor,
+                                        window_size,
+                                        camera,
+                                        transform,
+                                    )
+                                },
+                            );
+                            if let Some(ray) = ray {
+                                let mut closest = None;
+                                for (entity, size, target, transform) in
+                                    (&*entities, &sizes, &raycast_targets, &globals).join()
+                                {
+                                    if !target.input
+                                        || !visibilities.get(entity).map_or(true, |v| v.visible)
+                                    {
+                                        continue;
+                                    }
+                                    if let Some((distance, uv)) = target.intersect(&ray, transform)
+                                    {
+                                        let point = uv_to_page(uv, size.pixel_size());
+                                        match closest {
+                                            Some((d, _, _)) if d <= distance => {}
+                                            _ => closest = Some((distance, entity, point)),
+                                        }
+                                    }
+                                }
+                                if let Some((_, entity, point)) = closest {
+                                    targets.push((entity, point));
+                                }
+                            }
+                        }
+                        let input = match input {
+                            PointerInput::Released(button, click) => match self.pressed.take() {
+                                Some((entity, point)) => {
+                                    let over = targets
+                                        .iter()
+                                        .find(|&&(e, _)| e == entity)
+                                        .map(|&(_, point)| point);
+                                    targets = vec![(entity, over.unwrap_or(point))];
+                                    // Releasing elsewhere isn't a click on the page
+                                    PointerInput::Released(button, click && over.is_some())
+                                }
+                                None => PointerInput::Released(button, click),
+                            },
+                            input => input,
+                        };
+                        match input {
+                            PointerInput::Pressed(_) => self.pressed = targets.last().cloned(),
+                            PointerInput::Moved => if let Some((entity, ref mut point)) =
+                                self.pressed
+                            {
+                                let over = targets.iter().find(|&&(e, _)| e == entity);
+                                if let Some(&(_, over)) = over {
+                                    *point = over;
+                                }
+                            },
+                            _ => {}
+                        }
+                        for &(entity, point) in &targets {
+                            if let Some(handle) = servo_handles.get_mut(entity) {
+                                handle.send_events(pointer_events(&input, point));
+                            }
+                        }
+                        if let PointerInput::Pressed(_) = input {
+                            if focus.click_to_focus {
+                                match targets.last() {
+                                    Some(&(entity, _)) => focus.focus(entity),
+                                    None => focus.blur(),
+                                }
+                            }
+                        }
+                    }
+                    let keys = self.keyboard.update(event);
+                    if let Some(handle) = focus.entity.and_then(|e| servo_handles.get_mut(e)) {
+                        for (code, key_event) in keys {
+                            if let BrowserEvent::Key(_, key, state, _) = key_event {
+                                focus.forward_key(key, code, state == KeyState::Pressed);
+                            }
+                            handle.send_events(vec![key_event]);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // TEARDOWN ROUTINE
+        let alive = servo_handles
+            .join()
+            .map(|handle| handle.id)
+            .collect::<HashSet<_>>();
+        engine.close_browsers(&alive);
+        self.compositor.retain(&alive);
+        // Pages that were deleted or failed to start can't keep the keyboard
+        if let Some(entity) = focus.entity {
+            if servo_handles.get(entity).is_none() {
+                focus.blur();
+            }
+        }
+        focus.handled = focus.entity.is_some();
+
+        // HOT RELOAD ROUTINE
+        if let Some(ref mut watcher) = self.watcher {
+            watcher.retain(&alive);
+            let mut changed = watcher.poll(&assets);
             for (handle,) in (&mut servo_handles,).join() {
-                match event {
-                    &Event::Awakened => {
-                        handle.update();
+                let urls = match changed.remove(&handle.id) {
+                    Some(urls) => urls,
+                    None => continue,
+                };
+                let connected = handle.scripts.token.is_some();
+                if watcher.inject_css && connected && urls.iter().all(|url| is_stylesheet(url)) {
+                    // The page knows its stylesheets by the URLs it loaded them from
+                    let urls = urls.iter()
+                        .map(|url| engine.resolve_url(&assets, url))
+                        .collect();
+                    handle.scripts.reload_stylesheets(urls);
+                } else {
+                    handle.reload();
+                }
+            }
+        }
+
+        // COMPOSITE ROUTINE
+        // Idle pages cost nothing until Servo reports something about them, animating ones are
+        // ticked every frame. Hidden pages are only handed the events queued for them. Wakes
+        // no page has work for only let Servo process its messages, which composites the
+        // selected browser without switching to another one.
+        let mut pumped = false;
+        for (handle,) in (&mut servo_handles,).join() {
+            let tick = handle.visible && (handle.pending || handle.target.is_animating());
+            let zoomed = handle.target.get_zoom() != handle.target.get_applied_zoom();
+            if tick || zoomed || !handle.events.is_empty() {
+                if handle.visible {
+                    handle.pending = false;
+                }
+                let mut events = self.compositor.switch_to(handle.id, &handle.target);
+                events.extend(mem::replace(&mut handle.events, vec![]));
+                engine.handle_events(handle.id, events);
+                pumped = true;
+            }
+        }
+        if awakened && !pumped {
+            engine.pump();
+        }
+        for (handle, zoom) in (&servo_handles, &mut zooms).join() {
+            zoom.current = handle.target.get_applied_zoom();
+        }
+
+        // CAPTURE ROUTINE
+        for (handle,) in (&mut servo_handles,).join() {
+            for slot in handle.captures.drain(..) {
+                let image = engine.capture(handle.id);
+                if let Ok(mut slot) = slot.lock() {
+                    *slot = Some(image);
+                }
+            }
+        }
+
+        // EMBEDDER ROUTINE
+        let browsers = (&*entities, &servo_handles)
+            .join()
+            .map(|(entity, handle)| (handle.id, entity))
+            .collect::<HashMap<_, _>>();
+        for (browser, event) in engine.take_events() {
+            let entity = browser.and_then(|id| browsers.get(&id).cloned());
+            // Composited the next time the system runs, whether or not Servo wakes it
+            if let Some(handle) = entity.and_then(|e| servo_handles.get_mut(e)) {
+                handle.pending = true;
+            }
+            match event {
+                EmbedderMsg::KeyEvent(_, key, KeyState::Pressed, _) => {
+                    if entity.is_some() && focus.entity == entity {
+                        focus.unhandled_key(key);
+                    }
+                }
+                EmbedderMsg::HistoryChanged(entries, current) => {
+                    if let Some(entity) = entity {
+                        if let Some(handle) = servo_handles.get_mut(entity) {
+                            let previous = handle.current_url().map(|url| url.to_string());
+                            handle.history = entries
+                                .iter()
+                                .map(|url| engine.unresolve_url(&assets, url.as_str()))
+                                .collect();
+                            handle.history_index = current;
+                            let current = handle.current_url().map(|url| url.to_string());
+                            if let Some(current) = current {
+                                if previous.as_ref() != Some(&current) {
+                                    // Not dirty, the browser is already showing this page
+                                    if let Some(url) = urls.get_mut(entity) {
+                                        url.url = current.clone();
+                                    }
+                                    servo_events
+                                        .single_write(ServoEvent::UrlChanged(entity, current));
+                                }
+                            }
+                        }
+                        servo_events.single_write(ServoEvent::HistoryChanged(entity));
                     }
-                    _ => {}
                 }
+                EmbedderMsg::LoadStart => if let Some(entity) = entity {
+                    if let Some(handle) = servo_handles.get_mut(entity) {
+                        // Scripts sent to the old page will never report back
+                        if let (Some(token), Some(bridge)) =
+                            (handle.scripts.token.take(), engine.bridge())
+                        {
+                            bridge.forget(&token);
+                        }
+                        handle.scripts.disconnect();
+                    }
+                    statuses.insert(entity, ServoStatus::Loading);
+                    servo_events.single_write(ServoEvent::LoadStart(entity));
+                },
+                EmbedderMsg::LoadComplete => if let Some(entity) = entity {
+                    if let Some(handle) = servo_handles.get_mut(entity) {
+                        handle.scripts.loaded();
+                    }
+                    statuses.insert(entity, ServoStatus::Loaded);
+                    servo_events.single_write(ServoEvent::LoadComplete(entity));
+                },
+                EmbedderMsg::ChangePageTitle(title) => if let Some(entity) = entity {
+                    servo_events.single_write(ServoEvent::TitleChanged(entity, title));
+                },
+                // Reported once, as the error of the page rather than another Failed event
+                EmbedderMsg::Panic(reason, _backtrace) => if let Some(entity) = entity {
+                    let status = ServoStatus::Failed(ServoError::Panic(reason.clone()));
+                    statuses.insert(entity, status);
+                    servo_events.single_write(ServoEvent::LoadError(entity, reason));
+                },
+                EmbedderMsg::AllowNavigation(url, sender) => {
+                    let allow = if url.as_str().starts_with(&format!("{}:", BRIDGE_SCHEME)) {
+                        // The injected script introducing the page, not a real navigation.
+                        // Only pages the game serves itself are connected, unless remote
+                        // scripts are enabled.
+                        let handle = entity.and_then(|e| servo_handles.get_mut(e));
+                        if let (Some(handle), Some(bridge)) = (handle, engine.bridge()) {
+                            let local = handle.current_url().map_or(false, |page| {
+                                page.starts_with("file:")
+                                    || page.starts_with(&format!("{}:", assets.scheme()))
+                            });
+                            match bridge.connect_token(url.as_str()) {
+                                Some(ref token) if local || bridge.remote() => {
+                                    handle.scripts.connect(token.clone());
+                                }
+                                _ => eprintln!("Refused a page connecting to the bridge"),
+                            }
+                        }
+                        false
+                    } else if url.as_str().starts_with(&format!("{}:", assets.scheme())) {
+                        // Servo can't load asset URLs, load them from the bridge instead
+                        if let Some(handle) = entity.and_then(|e| servo_handles.get_mut(e)) {
+                            let url = engine.resolve_url(&assets, url.as_str());
+                            if let Err(e) = handle.navigate(&url) {
+                                eprintln!("Failed navigation: {}", e);
+                            }
+                        }
+                        false
+                    } else {
+                        true
+                    };
+                    if let Err(e) = sender.send(allow) {
+                        eprintln!("Failed to answer navigation request: {}", e);
+                    }
+                }
+                _ => {}
             }
         }
+
+        // SCRIPT ROUTINE
+        match engine.bridge() {
+            Some(bridge) => {
+                for (token, message) in bridge.receive() {
+                    let message: JsValue = match serde_json::from_str(&message) {
+                        Ok(message) => message,
+                        Err(e) => {
+                            eprintln!("Failed to parse page message: {}", e);
+                            continue;
+                        }
+                    };
+                    for (entity, handle) in (&*entities, &mut servo_handles).join() {
+                        if handle.scripts.token.as_ref() == Some(&token) {
+                            if let Some(message) = handle.scripts.receive(message) {
+                                if message["type"] == "message" {
+                                    let url = handle.current_url().unwrap_or("").to_string();
+                                    servo_messages.single_write(ServoMessage {
+                                        entity: entity,
+                                        url: url,
+                                        data: message["data"].clone(),
+                                    });
+                                } else if message["type"] == "resources" {
+                                    if let Some(ref mut watcher) = self.watcher {
+                                        let urls = message["urls"]
+                                            .as_array()
+                                            .map_or(&[][..], |urls| &urls[..])
+                                            .iter()
+                                            .filter_map(|url| url.as_str())
+                                            .map(|url| engine.unresolve_url(&assets, url))
+                                            .collect();
+                                        watcher.track(handle.id, urls, &assets);
+                                    }
+                                }
+                            }
+                            break;
+                        }
+                    }
+                }
+                for handle in (&mut servo_handles).join() {
+                    if let Some(token) = handle.scripts.token.clone() {
+                        for command in handle.scripts.drain() {
+                            bridge.send(&token, command);
+                        }
+                    }
+                }
+            }
+            None => for handle in (&mut servo_handles).join() {
+                handle.scripts.abandon();
+            },
+        }
     }
 }
+
+// Marks the browser as failed and reports it, rather than taking the game down with it
+fn fail(
+    entity: Entity,
+    error: ServoError,
+    statuses: &mut WriteStorage<ServoStatus>,
+    events: &mut EventChannel<ServoEvent>,
+) {
+    statuses.insert(entity, ServoStatus::Failed(error.clone()));
+    events.single_write(ServoEvent::Failed(entity, error));
+}
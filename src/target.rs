@@ -0,0 +1,445 @@
+use std::sync::{Arc, Mutex};
+use std::ops::{Deref, DerefMut};
+use libservo::gl;
+use amethyst::renderer::Texture;
+use gfx_device_gl::NewTexture;
+
+use super::ServoError;
+
+/// Render target of a single browser, Servo composites into whichever target is active on
+/// the ServoWindow
+#[derive(Clone)]
+pub struct ServoTarget {
+    // Needs interior mutability, so that resize event can mutate it
+    pub dimensions: Arc<Mutex<(u32, u32)>>,
+    pub target_texture: Arc<Mutex<Option<u32>>>,
+    pub buffers: Arc<Mutex<Option<(u32, u32)>>>,
+    pub hidpi_factor: Arc<Mutex<f32>>,
+    // Whether the target covers the game window, so its metrics are the ones to report
+    pub fit_window: Arc<Mutex<bool>>,
+    // Whether Servo reported the page as animating the last time it was composited
+    pub animating: Arc<Mutex<bool>>,
+    // Texture created for pages without a Material, deleted with the buffers
+    pub offscreen_texture: Arc<Mutex<Option<u32>>>,
+    // Straight RGBA the target is cleared to before Servo composites into it
+    pub background: Arc<Mutex<[f32; 4]>>,
+    // Page and pinch zoom wanted for the page, and the ones it was last composited at
+    pub zoom: Arc<Mutex<(f32, f32)>>,
+    pub applied_zoom: Arc<Mutex<(f32, f32)>>,
+}
+
+impl ServoTarget {
+    pub fn new() -> Self {
+        Self {
+            dimensions: Arc::new(Mutex::new((1024, 1024))),
+            target_texture: Arc::new(Mutex::new(None)),
+            buffers: Arc::new(Mutex::new(None)),
+            hidpi_factor: Arc::new(Mutex::new(1.)),
+            fit_window: Arc::new(Mutex::new(false)),
+            animating: Arc::new(Mutex::new(false)),
+            offscreen_texture: Arc::new(Mutex::new(None)),
+            background: Arc::new(Mutex::new([1., 1., 1., 1.])),
+            zoom: Arc::new(Mutex::new((1., 1.))),
+            applied_zoom: Arc::new(Mutex::new((1., 1.))),
+        }
+    }
+
+    pub fn get_dimensions(&self) -> (u32, u32) {
+        match self.dimensions.lock() {
+            Ok(d) => {
+                let d = &d.clone();
+                (d.0.clone(), d.1.clone())
+            }
+            Err(e) => {
+                eprintln!("ERROR: Dimension lock for Servo implementation was poisoned, servo UI is not guaranteed to scale correctly and may cause race conditions.");
+                let d = e.get_ref();
+                (d.0.clone(), d.1.clone())
+            }
+        }
+    }
+
+    pub fn set_dimensions(&self, width: u32, height: u32) {
+        match self.dimensions.lock() {
+            Ok(ref mut dimensions) => {
+                dimensions.0 = width;
+                dimensions.1 = height;
+            }
+            Err(_) => {
+                eprintln!("ERROR: Dimension lock for Servo implementation was poisoned, servo UI is not guaranteed to scale correctly and may cause race conditions.");
+            }
+        }
+    }
+
+    pub fn get_hidpi_factor(&self) -> f32 {
+        match self.hidpi_factor.lock() {
+            Ok(factor) => *factor,
+            Err(e) => {
+                eprintln!("ERROR: HiDPI factor lock poisoned.");
+                **e.get_ref()
+            }
+        }
+    }
+
+    pub fn set_hidpi_factor(&self, factor: f32) {
+        match self.hidpi_factor.lock() {
+            Ok(ref mut hidpi_factor) => {
+                **hidpi_factor = factor;
+            }
+            Err(_) => {
+                eprintln!("ERROR: HiDPI factor lock poisoned.");
+            }
+        }
+    }
+
+    pub fn fits_window(&self) -> bool {
+        match self.fit_window.lock() {
+            Ok(fit) => *fit,
+            Err(e) => {
+                eprintln!("ERROR: Fit window lock poisoned.");
+                **e.get_ref()
+            }
+        }
+    }
+
+    pub fn set_fit_window(&self, fit: bool) {
+        match self.fit_window.lock() {
+            Ok(ref mut fit_window) => {
+                **fit_window = fit;
+            }
+            Err(_) => {
+                eprintln!("ERROR: Fit window lock poisoned.");
+            }
+        }
+    }
+
+    pub fn is_animating(&self) -> bool {
+        match self.animating.lock() {
+            Ok(animating) => *animating,
+            Err(e) => {
+                eprintln!("ERROR: Animation lock poisoned.");
+                **e.get_ref()
+            }
+        }
+    }
+
+    pub fn set_animating(&self, animating: bool) {
+        match self.animating.lock() {
+            Ok(ref mut state) => {
+                **state = animating;
+            }
+            Err(_) => {
+                eprintln!("ERROR: Animation lock poisoned.");
+            }
+        }
+    }
+
+    pub fn get_background(&self) -> [f32; 4] {
+        match self.background.lock() {
+            Ok(background) => *background,
+            Err(e) => {
+                eprintln!("ERROR: Background lock poisoned.");
+                **e.get_ref()
+            }
+        }
+    }
+
+    pub fn set_background(&self, color: [f32; 4]) {
+        match self.background.lock() {
+            Ok(ref mut background) => {
+                **background = color;
+            }
+            Err(_) => {
+                eprintln!("ERROR: Background lock poisoned.");
+            }
+        }
+    }
+
+    pub fn get_zoom(&self) -> (f32, f32) {
+        match self.zoom.lock() {
+            Ok(zoom) => *zoom,
+            Err(e) => {
+                eprintln!("ERROR: Zoom lock poisoned.");
+                **e.get_ref()
+            }
+        }
+    }
+
+    pub fn set_zoom(&self, zoom: (f32, f32)) {
+        match self.zoom.lock() {
+            Ok(ref mut state) => {
+                **state = zoom;
+            }
+            Err(_) => {
+                eprintln!("ERROR: Zoom lock poisoned.");
+            }
+        }
+    }
+
+    pub fn get_applied_zoom(&self) -> (f32, f32) {
+        match self.applied_zoom.lock() {
+            Ok(zoom) => *zoom,
+            Err(e) => {
+                eprintln!("ERROR: Zoom lock poisoned.");
+                **e.get_ref()
+            }
+        }
+    }
+
+    pub fn set_applied_zoom(&self, zoom: (f32, f32)) {
+        match self.applied_zoom.lock() {
+            Ok(ref mut state) => {
+                **state = zoom;
+            }
+            Err(_) => {
+                eprintln!("ERROR: Zoom lock poisoned.");
+            }
+        }
+    }
+
+    pub fn set_target(&self, targ: &Texture) {
+        extern crate gfx_device_gl;
+        let targ = targ.raw().deref().resource();
+        match self.target_texture.lock() {
+            Ok(ref mut target) => {
+                let mut target = target.deref_mut();
+                match targ {
+                    &NewTexture::Texture(t) => {
+                        *target = Some(t);
+                    }
+                    _ => {}
+                }
+            }
+            Err(_) => {
+                eprintln!("ERROR: Target texture lock poisoned.");
+            }
+        }
+    }
+
+    pub fn remove_target(&self) -> Result<(), ServoError> {
+        match self.target_texture.lock() {
+            Ok(ref mut target) => {
+                let mut target = target.deref_mut();
+                *target = None;
+                Ok(())
+            }
+            Err(_) => Err(ServoError::LockPoisoned),
+        }
+    }
+
+    pub fn has_target(&self) -> Result<bool, ServoError> {
+        match self.target_texture.lock() {
+            Ok(target) => match *target {
+                Some(_) => Ok(true),
+                None => Ok(false),
+            },
+            Err(_) => {
+                eprintln!("ERROR: Target texture lock poisoned.");
+                Err(ServoError::LockPoisoned)
+            }
+        }
+    }
+
+    pub fn get_target(&self) -> Option<u32> {
+        match self.target_texture.lock() {
+            Ok(ref target) => target.deref().clone(),
+            Err(ref e) => {
+                eprintln!("ERROR: Target texture lock poisoned..");
+                e.get_ref().deref().clone()
+            }
+        }
+    }
+
+    pub fn setup_framebuffer(&self, gl: &gl::Gl, target: &Texture) -> Result<(), ServoError> {
+        // The texture belongs to the amethyst asset storage, only the buffers are ours
+        self.release_buffers(gl);
+        self.set_target(target);
+        match self.get_target() {
+            Some(texture) => self.attach(gl, texture),
+            None => Err(ServoError::GlContext("Target is not a plain texture".into())),
+        }
+    }
+
+    /// Creates a texture for the page to render into, when there is no amethyst texture
+    pub fn setup_offscreen(&self, gl: &gl::Gl) -> Result<(), ServoError> {
+        self.release_buffers(gl);
+        let (width, height) = self.get_dimensions();
+        let texture = gl.gen_textures(1)[0];
+        gl.bind_texture(gl::TEXTURE_2D, texture);
+        gl.tex_image_2d(
+            gl::TEXTURE_2D,
+            0,
+            gl::RGBA8 as i32,
+            width as i32,
+            height as i32,
+            0,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            None,
+        );
+        gl.tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+        gl.tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        match (self.offscreen_texture.lock(), self.target_texture.lock()) {
+            (Ok(ref mut offscreen), Ok(ref mut target)) => {
+                **offscreen = Some(texture);
+                **target = Some(texture);
+            }
+            _ => {
+                gl.delete_textures(&[texture]);
+                return Err(ServoError::LockPoisoned);
+            }
+        }
+        self.attach(gl, texture)
+    }
+
+    // Creates the framebuffer and depth buffer rendering into `texture`
+    fn attach(&self, gl: &gl::Gl, texture: u32) -> Result<(), ServoError> {
+        // Fetch required width and height
+        let (width, height) = self.get_dimensions();
+
+        // Create FBO
+        let frame_buffer = gl.gen_framebuffers(1)[0];
+        gl.bind_framebuffer(gl::DRAW_FRAMEBUFFER, frame_buffer);
+
+        // Texture
+        gl.bind_texture(gl::TEXTURE_2D, texture);
+        gl.framebuffer_texture_2d(
+            gl::DRAW_FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT0,
+            gl::TEXTURE_2D,
+            texture.into(),
+            0,
+        );
+
+        // Create depth buffer
+        let depth_buffer = gl.gen_renderbuffers(1)[0];
+        gl.bind_renderbuffer(gl::RENDERBUFFER, depth_buffer);
+        gl.renderbuffer_storage(
+            gl::RENDERBUFFER,
+            gl::DEPTH_COMPONENT,
+            width as i32,
+            height as i32,
+        );
+        // Bind depth buffer to FBO
+        gl.framebuffer_renderbuffer(
+            gl::DRAW_FRAMEBUFFER,
+            gl::DEPTH_ATTACHMENT,
+            gl::RENDERBUFFER,
+            depth_buffer,
+        );
+
+        // Cleanup
+        match gl.check_frame_buffer_status(gl::DRAW_FRAMEBUFFER) {
+            gl::FRAMEBUFFER_COMPLETE => match self.buffers.lock() {
+                Ok(mut fb) => {
+                    gl.insert_event_marker_ext(&"Finished setting up servo FBO resources");
+                    *fb = Some((frame_buffer, depth_buffer));
+                    Ok(())
+                }
+                Err(_) => {
+                    gl.delete_framebuffers(&[frame_buffer]);
+                    gl.delete_renderbuffers(&[depth_buffer]);
+                    gl.insert_event_marker_ext(&"Failed setting up servo FBO resources");
+                    Err(ServoError::LockPoisoned)
+                }
+            },
+            e => {
+                gl.delete_framebuffers(&[frame_buffer]);
+                gl.delete_renderbuffers(&[depth_buffer]);
+                gl.insert_event_marker_ext(&"Failed setting up servo FBO resources");
+                Err(ServoError::FramebufferIncomplete(e))
+            }
+        }
+    }
+
+    /// Deletes the framebuffer, depth buffer and offscreen texture and forgets the albedo
+    /// texture, which is freed by amethyst once the Material referencing it is dropped
+    pub fn release(&self, gl: &gl::Gl) {
+        self.release_buffers(gl);
+        if let Err(e) = self.remove_target() {
+            eprintln!("Failed to release render target: {}", e);
+        }
+    }
+
+    fn release_buffers(&self, gl: &gl::Gl) {
+        match self.offscreen_texture.lock() {
+            Ok(ref mut offscreen) => {
+                if let Some(texture) = offscreen.take() {
+                    gl.delete_textures(&[texture]);
+                }
+            }
+            Err(_) => {
+                eprintln!("ERROR: Offscreen texture lock poisoned.");
+            }
+        }
+        match self.buffers.lock() {
+            Ok(ref mut buffers) => {
+                if let Some((framebuffer, renderbuffer)) = buffers.take() {
+                    gl.delete_framebuffers(&[framebuffer]);
+                    gl.delete_renderbuffers(&[renderbuffer]);
+                }
+            }
+            Err(_) => {
+                eprintln!("ERROR: Framebuffer lock poisoned.");
+            }
+        }
+    }
+
+    /// Reads the render target back as RGBA, top row first
+    pub fn read_pixels(&self, gl: &gl::Gl) -> Result<(u32, u32, Vec<u8>), ServoError> {
+        let framebuffer = match self.buffers.lock() {
+            Ok(guard) => match *guard {
+                Some((framebuffer, _renderbuffer)) => framebuffer,
+                None => return Err(ServoError::GlContext("Render target is not set up".into())),
+            },
+            Err(_) => return Err(ServoError::LockPoisoned),
+        };
+        let (width, height) = self.get_dimensions();
+        gl.bind_framebuffer(gl::READ_FRAMEBUFFER, framebuffer);
+        gl.pixel_store_i(gl::PACK_ALIGNMENT, 1);
+        let pixels = gl.read_pixels(0, 0, width as i32, height as i32, gl::RGBA, gl::UNSIGNED_BYTE);
+        gl.bind_framebuffer(gl::READ_FRAMEBUFFER, 0);
+
+        // GL rows start at the bottom, and images are expected with straight alpha
+        let stride = width as usize * 4;
+        let data = pixels
+            .chunks(stride)
+            .rev()
+            .flat_map(|row| row.chunks(4).flat_map(unpremultiply))
+            .collect();
+        Ok((width, height, data))
+    }
+
+    pub fn enable_fb(&self, gl: &gl::Gl) -> Result<(), ()> {
+        match self.buffers.lock() {
+            Ok(guard) => match *guard {
+                Some((framebuffer, _renderbuffer)) => {
+                    gl.insert_event_marker_ext(&"Binding FBO target for servo");
+                    gl.bind_framebuffer(gl::DRAW_FRAMEBUFFER, framebuffer);
+                    gl.draw_buffers(&[gl::COLOR_ATTACHMENT0]);
+                    gl.disable(gl::CULL_FACE);
+                    gl.depth_func(gl::LESS);
+                    // Servo renders premultiplied alpha, so the background is premultiplied too
+                    let c = self.get_background();
+                    gl.clear_color(c[0] * c[3], c[1] * c[3], c[2] * c[3], c[3]);
+                    gl.clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+
+                    Ok(())
+                }
+                None => Err(()),
+            },
+            Err(_) => Err(()),
+        }
+    }
+}
+
+fn unpremultiply(pixel: &[u8]) -> Vec<u8> {
+    let alpha = pixel[3] as u32;
+    match alpha {
+        0 | 255 => pixel.to_vec(),
+        _ => pixel[..3]
+            .iter()
+            .map(|&c| ((c as u32 * 255 + alpha / 2) / alpha).min(255) as u8)
+            .chain(Some(pixel[3]))
+            .collect(),
+    }
+}
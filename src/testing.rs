@@ -0,0 +1,491 @@
+//! Runs pages without a game window and compares what they render with reference images,
+//! enabled by the `testing` feature
+
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use amethyst::assets::{AssetStorage, Loader};
+use amethyst::core::transform::GlobalTransform;
+use amethyst::ecs::{Entity, RunNow};
+use amethyst::prelude::World;
+use amethyst::renderer::{Camera, Material, ScreenDimensions, Texture};
+use amethyst::shrev::EventChannel;
+use amethyst::winit::Event;
+use glutin::{GlContext, HeadlessContext, HeadlessRendererBuilder};
+use libservo::compositing::compositor_thread::EventLoopWaker;
+use libservo::embedder_traits::EmbedderMsg;
+use libservo::msg::constellation_msg::{PipelineNamespace, PipelineNamespaceId,
+                                       TopLevelBrowsingContextId};
+use rayon::{Configuration, ThreadPool};
+
+use super::{Bridge, ServoAssets, ServoBackend, ServoConfig, ServoError, ServoHandle, ServoHost,
+            ServoImage, ServoSize, ServoStatus, ServoUiSystem, ServoUrl};
+use super::bundle;
+use super::handle::BrowserEvent;
+
+/// Set to write the captured image as the new reference instead of comparing with it
+pub const BLESS_VAR: &str = "AMETHYST_SERVO_BLESS";
+
+const STEP_INTERVAL: u64 = 5;
+const CAPTURE_TIMEOUT: u64 = 5;
+
+/// Hosts Servo in an offscreen GL context
+pub struct HeadlessHost {
+    context: HeadlessContext,
+}
+
+impl HeadlessHost {
+    pub fn new(width: u32, height: u32) -> Result<Self, ServoError> {
+        match HeadlessRendererBuilder::new(width, height).build() {
+            Ok(context) => Ok(Self { context: context }),
+            Err(e) => Err(ServoError::GlContext(format!("{}", e))),
+        }
+    }
+}
+
+impl ServoHost for HeadlessHost {
+    fn make_current(&self) -> Result<(), String> {
+        unsafe { self.context.make_current() }.map_err(|e| format!("{}", e))
+    }
+
+    fn get_proc_address(&self, name: &str) -> *const () {
+        self.context.get_proc_address(name)
+    }
+
+    fn hidpi_factor(&self) -> f32 {
+        1.
+    }
+
+    fn window_metrics(&self) -> Option<((u32, u32), (u32, u32), (i32, i32))> {
+        None
+    }
+
+    fn create_event_loop_waker(&self) -> Box<EventLoopWaker> {
+        Box::new(NoopWaker)
+    }
+}
+
+// The harness steps every frame as if it had been woken, so there is nothing to wake
+struct NoopWaker;
+
+impl EventLoopWaker for NoopWaker {
+    fn clone(&self) -> Box<EventLoopWaker + Send> {
+        Box::new(NoopWaker)
+    }
+    fn wake(&self) {}
+}
+
+/// A world with the components and resources the ServoUiSystem reads, without a window or
+/// a renderer
+pub fn test_world() -> Result<World, ServoError> {
+    let pool = match ThreadPool::new(Configuration::new()) {
+        Ok(pool) => Arc::new(pool),
+        Err(e) => return Err(ServoError::EngineStartup(format!("{}", e))),
+    };
+    let mut world = World::new();
+    bundle::register(&mut world);
+    world.register::<Material>();
+    world.register::<Camera>();
+    world.register::<GlobalTransform>();
+    world.add_resource(AssetStorage::<Texture>::new());
+    world.add_resource(Loader::new(env::current_dir().unwrap_or_default(), pool));
+    world.add_resource(ScreenDimensions::new(1, 1));
+    world.add_resource(EventChannel::<Event>::new());
+    Ok(world)
+}
+
+/// A world with only the ServoUiSystem in it, stepped by hand. Pages opened with `open` have
+/// no Material, so they are rendered offscreen and can only be captured.
+pub struct ServoTestHarness {
+    world: World,
+    system: ServoUiSystem,
+}
+
+impl ServoTestHarness {
+    pub fn new() -> Result<Self, ServoError> {
+        Self::with_config(ServoConfig::default())
+    }
+
+    pub fn with_config(config: ServoConfig) -> Result<Self, ServoError> {
+        let host = HeadlessHost::new(1, 1)?;
+        let mut world = test_world()?;
+        let system = ServoUiSystem::with_host(&mut world, config, Rc::new(host));
+        Ok(Self {
+            world: world,
+            system: system,
+        })
+    }
+
+    /// Add asset sources or other resources through the world before opening pages
+    pub fn world(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Opens a page `width` by `height` device pixels large
+    pub fn open(&mut self, url: &str, width: u32, height: u32) -> Entity {
+        self.world
+            .create_entity()
+            .with(ServoUrl::from(url))
+            .with(ServoSize::new(width, height).with_device_pixel_ratio(1.))
+            .build()
+    }
+
+    /// Runs the ServoUiSystem once, as if Servo had woken the event loop
+    pub fn step(&mut self) {
+        self.world
+            .write_resource::<EventChannel<Event>>()
+            .single_write(Event::Awakened);
+        self.system.run_now(&self.world.res);
+        self.world.maintain();
+    }
+
+    /// Steps `frames` times, for pages that keep changing after they loaded
+    pub fn settle(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.step();
+            thread::sleep(Duration::from_millis(STEP_INTERVAL));
+        }
+    }
+
+    /// Steps until the page has loaded or failed
+    pub fn wait_for_load(&mut self, entity: Entity, timeout: Duration) -> Result<(), ServoError> {
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            self.step();
+            // Read from storage, so waiting for one page leaves the others' events alone
+            match self.world.read_storage::<ServoStatus>().get(entity) {
+                Some(&ServoStatus::Loaded) => return Ok(()),
+                Some(&ServoStatus::Failed(ref error)) => return Err(error.clone()),
+                _ => {}
+            }
+            thread::sleep(Duration::from_millis(STEP_INTERVAL));
+        }
+        Err(ServoError::Timeout)
+    }
+
+    /// Reads back what the page last composited
+    pub fn capture(&mut self, entity: Entity) -> Result<ServoImage, ServoError> {
+        let capture = match self.world.write_storage::<ServoHandle>().get_mut(entity) {
+            Some(handle) => handle.capture(),
+            None => return Err(ServoError::BrowserClosed),
+        };
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(CAPTURE_TIMEOUT) {
+            self.step();
+            if let Some(result) = capture.try_get() {
+                return result;
+            }
+        }
+        Err(ServoError::Timeout)
+    }
+}
+
+/// How far an image is from its reference
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageDiff {
+    /// Pixels with a channel more than the tolerance apart
+    pub mismatched: usize,
+    /// Largest difference of any channel
+    pub max_difference: u8,
+}
+
+/// Compares two images channel by channel, fails if their sizes differ
+pub fn compare(
+    image: &ServoImage,
+    reference: &ServoImage,
+    tolerance: u8,
+) -> Result<ImageDiff, String> {
+    if (image.width, image.height) != (reference.width, reference.height) {
+        return Err(format!(
+            "Image is {}x{}, the reference is {}x{}",
+            image.width, image.height, reference.width, reference.height
+        ));
+    }
+    let mut diff = ImageDiff {
+        mismatched: 0,
+        max_difference: 0,
+    };
+    for (a, b) in image.data.chunks(4).zip(reference.data.chunks(4)) {
+        let difference = a.iter()
+            .zip(b.iter())
+            .map(|(a, b)| if a > b { a - b } else { b - a })
+            .max()
+            .unwrap_or(0);
+        if difference > tolerance {
+            diff.mismatched += 1;
+        }
+        diff.max_difference = diff.max_difference.max(difference);
+    }
+    Ok(diff)
+}
+
+/// Panics unless `image` matches the PNG at `path`. A mismatching image is written next to
+/// the reference with an `.actual.png` extension. Set AMETHYST_SERVO_BLESS to write the
+/// image as the reference instead.
+pub fn assert_golden<P: AsRef<Path>>(image: &ServoImage, path: P, tolerance: u8) {
+    let path = path.as_ref();
+    if env::var_os(BLESS_VAR).is_some() {
+        let saved = match path.parent() {
+            Some(parent) => fs::create_dir_all(parent).and_then(|_| image.save_png(path)),
+            None => image.save_png(path),
+        };
+        if let Err(e) = saved {
+            panic!("Failed to write {}: {}", path.display(), e);
+        }
+        return;
+    }
+    let reference = match ServoImage::load_png(path) {
+        Ok(reference) => reference,
+        Err(e) => panic!(
+            "Failed to read {}: {}, set {} to create it",
+            path.display(),
+            e,
+            BLESS_VAR
+        ),
+    };
+    let failure = match compare(image, &reference, tolerance) {
+        Ok(ref diff) if diff.mismatched == 0 => return,
+        Ok(diff) => format!(
+            "{} pixels differ by up to {}",
+            diff.mismatched, diff.max_difference
+        ),
+        Err(e) => e,
+    };
+    let actual = path.with_extension("actual.png");
+    if let Err(e) = image.save_png(&actual) {
+        eprintln!("Failed to write {}: {}", actual.display(), e);
+    }
+    panic!(
+        "{} doesn't match {}: {}",
+        actual.display(),
+        path.display(),
+        failure
+    );
+}
+
+/// Request the ServoUiSystem made of a MockBackend
+#[derive(Clone, Debug, PartialEq)]
+pub enum MockCall {
+    NewBrowser(String),
+    SetupTarget,
+    SetupOffscreenTarget,
+    /// The system switched the compositor to the browser
+    Select(TopLevelBrowsingContextId),
+    /// The page was laid out at the size of its target
+    Resize(TopLevelBrowsingContextId, (u32, u32)),
+    /// The page zoom was multiplied by the factor
+    Zoom(TopLevelBrowsingContextId, f32),
+    ResetZoom(TopLevelBrowsingContextId),
+    /// The pinch zoom was multiplied by the factor
+    PinchZoom(TopLevelBrowsingContextId, f32),
+    LoadUrl(TopLevelBrowsingContextId, String),
+    Visibility(TopLevelBrowsingContextId, bool),
+    /// Any other event, such as input or history traversal
+    Event(TopLevelBrowsingContextId),
+    /// The browser was handed its events and composited
+    Composite(TopLevelBrowsingContextId),
+    Close(TopLevelBrowsingContextId),
+    Pump,
+}
+
+struct MockState {
+    calls: Vec<MockCall>,
+    // Size every open browser was last laid out at
+    browsers: HashMap<TopLevelBrowsingContextId, (u32, u32)>,
+    events: Vec<(Option<TopLevelBrowsingContextId>, EmbedderMsg)>,
+    failure: Option<ServoError>,
+    target_failure: Option<ServoError>,
+}
+
+thread_local! {
+    // Browser ids can only be made once a pipeline namespace is installed on the thread
+    static NAMESPACE_INSTALLED: Cell<bool> = Cell::new(false);
+}
+
+/// Backend recording what the ServoUiSystem asks of it instead of running Servo, for testing
+/// the system without a GL context. Clones share their state, so a clone kept by the test sees
+/// the calls made on the one given to ServoUiSystem::with_engine.
+#[derive(Clone)]
+pub struct MockBackend {
+    state: Rc<RefCell<MockState>>,
+    bridge: Option<Rc<Bridge>>,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self {
+            state: Rc::new(RefCell::new(MockState {
+                calls: vec![],
+                browsers: HashMap::new(),
+                events: vec![],
+                failure: None,
+                target_failure: None,
+            })),
+            bridge: None,
+        }
+    }
+
+    /// Serves the assets and connects pages over a loopback bridge like ServoEngine does, the
+    /// test plays the page by talking to the bridge on `bridge_port`
+    pub fn with_bridge(assets: &ServoAssets) -> io::Result<Self> {
+        let bridge = Bridge::start(assets, false)?;
+        Ok(Self {
+            bridge: Some(Rc::new(bridge)),
+            ..Self::new()
+        })
+    }
+
+    pub fn bridge_port(&self) -> Option<u16> {
+        self.bridge.as_ref().map(|bridge| bridge.port())
+    }
+
+    /// Secret the injected script puts in the paths it requests, `<secret>/<token>`
+    pub fn bridge_secret(&self) -> Option<String> {
+        self.bridge.as_ref().map(|bridge| bridge.secret().to_string())
+    }
+
+    /// Calls made since the last call to take_calls
+    pub fn take_calls(&self) -> Vec<MockCall> {
+        self.state.borrow_mut().calls.drain(..).collect()
+    }
+
+    /// Browsers that were started and not closed yet
+    pub fn browsers(&self) -> Vec<TopLevelBrowsingContextId> {
+        self.state.borrow().browsers.keys().cloned().collect()
+    }
+
+    /// Queues a message as if Servo had sent it about the browser
+    pub fn send(&self, browser: Option<TopLevelBrowsingContextId>, message: EmbedderMsg) {
+        self.state.borrow_mut().events.push((browser, message));
+    }
+
+    /// Makes the next browser fail to start with the error
+    pub fn fail_next_browser(&self, error: ServoError) {
+        self.state.borrow_mut().failure = Some(error);
+    }
+
+    /// Makes setting up the next target fail with the error
+    pub fn fail_next_target(&self, error: ServoError) {
+        self.state.borrow_mut().target_failure = Some(error);
+    }
+
+    fn record(&self, call: MockCall) {
+        self.state.borrow_mut().calls.push(call);
+    }
+}
+
+impl ServoBackend for MockBackend {
+    fn hidpi_factor(&self) -> f32 {
+        1.
+    }
+
+    fn new_browser(&mut self, _: &ServoAssets, url: &str) -> Result<ServoHandle, ServoError> {
+        self.record(MockCall::NewBrowser(url.to_string()));
+        if let Some(error) = self.state.borrow_mut().failure.take() {
+            return Err(error);
+        }
+        NAMESPACE_INSTALLED.with(|installed| {
+            if !installed.get() {
+                PipelineNamespace::install(PipelineNamespaceId(u32::max_value()));
+                installed.set(true);
+            }
+        });
+        let handle = ServoHandle::new(TopLevelBrowsingContextId::new());
+        self.state
+            .borrow_mut()
+            .browsers
+            .insert(handle.id, (0, 0));
+        Ok(handle)
+    }
+
+    fn setup_target(&self, _: TopLevelBrowsingContextId, _: &Texture) -> Result<(), ServoError> {
+        self.record(MockCall::SetupTarget);
+        match self.state.borrow_mut().target_failure.take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    fn setup_offscreen_target(&self, _: TopLevelBrowsingContextId) -> Result<(), ServoError> {
+        self.record(MockCall::SetupOffscreenTarget);
+        match self.state.borrow_mut().target_failure.take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    fn close_browsers(&mut self, alive: &HashSet<TopLevelBrowsingContextId>) {
+        let closed = self.browsers()
+            .into_iter()
+            .filter(|id| !alive.contains(id))
+            .collect::<Vec<_>>();
+        for id in closed {
+            self.state.borrow_mut().browsers.remove(&id);
+            self.record(MockCall::Close(id));
+        }
+    }
+
+    fn handle_events(&mut self, id: TopLevelBrowsingContextId, events: Vec<BrowserEvent>) {
+        self.record(MockCall::Composite(id));
+        for event in events {
+            self.record(match event {
+                BrowserEvent::LoadUrl(url) => MockCall::LoadUrl(id, url.as_str().to_string()),
+                BrowserEvent::Visibility(visible) => MockCall::Visibility(id, visible),
+                BrowserEvent::Select => MockCall::Select(id),
+                BrowserEvent::Resize(size, _) => {
+                    self.state.borrow_mut().browsers.insert(id, size);
+                    MockCall::Resize(id, size)
+                }
+                BrowserEvent::Zoom(magnification) => MockCall::Zoom(id, magnification),
+                BrowserEvent::ResetZoom => MockCall::ResetZoom(id),
+                BrowserEvent::PinchZoom(magnification) => MockCall::PinchZoom(id, magnification),
+                _ => MockCall::Event(id),
+            });
+        }
+    }
+
+    fn pump(&mut self) {
+        self.record(MockCall::Pump);
+    }
+
+    fn capture(&self, id: TopLevelBrowsingContextId) -> Result<ServoImage, ServoError> {
+        let (width, height) = match self.state.borrow().browsers.get(&id) {
+            Some(&size) => size,
+            None => return Err(ServoError::BrowserClosed),
+        };
+        Ok(ServoImage {
+            width: width,
+            height: height,
+            data: vec![0; (width * height * 4) as usize],
+        })
+    }
+
+    fn take_events(&mut self) -> Vec<(Option<TopLevelBrowsingContextId>, EmbedderMsg)> {
+        self.state.borrow_mut().events.drain(..).collect()
+    }
+
+    fn bridge(&self) -> Option<&Bridge> {
+        self.bridge.as_ref().map(|bridge| &**bridge)
+    }
+
+    fn resolve_url(&self, assets: &ServoAssets, url: &str) -> String {
+        match self.bridge_port() {
+            Some(port) => assets.resolve(url, port),
+            None => url.to_string(),
+        }
+    }
+
+    fn unresolve_url(&self, assets: &ServoAssets, url: &str) -> String {
+        match self.bridge_port() {
+            Some(port) => assets.unresolve(url, port),
+            None => url.to_string(),
+        }
+    }
+}
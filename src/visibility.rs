@@ -0,0 +1,29 @@
+use amethyst::ecs::{Component, VecStorage};
+
+/// Shows or hides a browser entity, entities without it are visible. Hidden pages are not
+/// drawn, get no input and are throttled by Servo, their timers and animations slow down and
+/// they aren't composited until they are shown again.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ServoVisibility {
+    pub visible: bool,
+}
+
+impl Component for ServoVisibility {
+    type Storage = VecStorage<ServoVisibility>;
+}
+
+impl Default for ServoVisibility {
+    fn default() -> Self {
+        Self::visible()
+    }
+}
+
+impl ServoVisibility {
+    pub fn visible() -> Self {
+        Self { visible: true }
+    }
+
+    pub fn hidden() -> Self {
+        Self { visible: false }
+    }
+}
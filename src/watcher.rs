@@ -0,0 +1,79 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use libservo::msg::constellation_msg::TopLevelBrowsingContextId;
+
+use super::ServoAssets;
+
+const POLL_INTERVAL: u64 = 500;
+
+/// Polls the files pages were built from, enabled by ServoConfig::hot_reload. Pages report
+/// their document, stylesheets, scripts and images through the bridge once they have loaded.
+pub(crate) struct Watcher {
+    /// Swap changed stylesheets in place instead of reloading the page
+    pub inject_css: bool,
+    polled: Instant,
+    // Modification time of every file a browser reported, by URL
+    files: HashMap<TopLevelBrowsingContextId, HashMap<String, u64>>,
+}
+
+impl Watcher {
+    pub fn new(inject_css: bool) -> Self {
+        Self {
+            inject_css: inject_css,
+            polled: Instant::now(),
+            files: HashMap::new(),
+        }
+    }
+
+    /// Starts watching the files of a page, forgetting those of the page it replaced. URLs
+    /// that aren't asset or `file://` URLs are ignored.
+    pub fn track(
+        &mut self,
+        id: TopLevelBrowsingContextId,
+        urls: Vec<String>,
+        assets: &ServoAssets,
+    ) {
+        let files = urls.into_iter()
+            .filter_map(|url| {
+                let url = url.split(|c| c == '?' || c == '#').next().unwrap_or("");
+                assets
+                    .modified(url)
+                    .map(|modified| (url.to_string(), modified))
+            })
+            .collect();
+        self.files.insert(id, files);
+    }
+
+    pub fn retain(&mut self, alive: &HashSet<TopLevelBrowsingContextId>) {
+        self.files.retain(|id, _| alive.contains(id));
+    }
+
+    /// Files that changed since they were last seen, by browser. Files are only checked every
+    /// POLL_INTERVAL milliseconds, in between this returns nothing.
+    pub fn poll(
+        &mut self,
+        assets: &ServoAssets,
+    ) -> HashMap<TopLevelBrowsingContextId, Vec<String>> {
+        let mut changed = HashMap::new();
+        if self.polled.elapsed() < Duration::from_millis(POLL_INTERVAL) {
+            return changed;
+        }
+        self.polled = Instant::now();
+        for (id, files) in self.files.iter_mut() {
+            for (url, modified) in files.iter_mut() {
+                match assets.modified(url) {
+                    Some(time) if time != *modified => {
+                        *modified = time;
+                        changed.entry(*id).or_insert_with(Vec::new).push(url.clone());
+                    }
+                    _ => {}
+                }
+            }
+        }
+        changed
+    }
+}
+
+pub(crate) fn is_stylesheet(url: &str) -> bool {
+    url.to_lowercase().ends_with(".css")
+}
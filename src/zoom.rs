@@ -0,0 +1,64 @@
+use amethyst::ecs::{Component, VecStorage};
+
+/// Smallest and largest zoom Servo accepts
+pub const MIN_ZOOM: f32 = 0.1;
+pub const MAX_ZOOM: f32 = 8.;
+/// Servo never pinch zooms out of the viewport
+pub const MIN_PINCH_ZOOM: f32 = 1.;
+
+/// Zoom of a browser entity, entities without it aren't zoomed. Page zoom scales CSS pixels
+/// like the zoom of a desktop browser and reflows the page, pinch zoom magnifies the page
+/// without reflowing it, like pinching a touch screen.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServoZoom {
+    pub page: f32,
+    pub pinch: f32,
+    // Zoom the page was last composited at, filled in by the ServoUiSystem
+    #[serde(skip)]
+    pub(crate) current: (f32, f32),
+}
+
+impl Component for ServoZoom {
+    type Storage = VecStorage<ServoZoom>;
+}
+
+impl Default for ServoZoom {
+    fn default() -> Self {
+        Self::new(1.)
+    }
+}
+
+impl ServoZoom {
+    pub fn new(page: f32) -> Self {
+        Self {
+            page: page,
+            pinch: 1.,
+            current: (1., 1.),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.page = 1.;
+        self.pinch = 1.;
+    }
+
+    /// Page zoom the page is shown at, `page` clamped to what Servo accepts once the page has
+    /// been composited
+    pub fn page_zoom(&self) -> f32 {
+        self.current.0
+    }
+
+    /// Pinch zoom the page is shown at
+    pub fn pinch_zoom(&self) -> f32 {
+        self.current.1
+    }
+
+    /// Page and pinch zoom clamped to what Servo accepts
+    pub(crate) fn clamped(&self) -> (f32, f32) {
+        (
+            self.page.max(MIN_ZOOM).min(MAX_ZOOM),
+            self.pinch.max(MIN_PINCH_ZOOM).min(MAX_ZOOM),
+        )
+    }
+}
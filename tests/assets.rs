@@ -0,0 +1,29 @@
+extern crate amethyst;
+extern crate amethyst_servo;
+
+use amethyst::assets::Directory;
+use amethyst_servo::ServoAssets;
+
+fn assets() -> ServoAssets {
+    let pages = format!("{}/tests/pages", env!("CARGO_MANIFEST_DIR"));
+    ServoAssets::new().with_default_source(Directory::new(pages))
+}
+
+#[test]
+fn finds_files_inside_the_source() {
+    let assets = assets();
+    assert!(assets.modified("amethyst:///solid.html").is_some());
+    assert!(assets.modified("amethyst:///solid.html?v=2#top").is_some());
+}
+
+#[test]
+fn rejects_paths_leaving_the_source() {
+    let assets = assets();
+    assert_eq!(assets.modified("amethyst:///../golden.rs"), None);
+    assert_eq!(assets.modified("amethyst:///%2e%2e/golden.rs"), None);
+    assert_eq!(assets.modified("amethyst:///./solid.html"), None);
+    assert_eq!(assets.modified("amethyst:////etc/passwd"), None);
+    assert_eq!(assets.modified("amethyst:///%2Fetc/passwd"), None);
+    assert_eq!(assets.modified("amethyst:///%5Cetc/passwd"), None);
+    assert_eq!(assets.modified("amethyst:///..%5Cgolden.rs"), None);
+}
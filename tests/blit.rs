@@ -0,0 +1,52 @@
+extern crate amethyst_servo;
+
+use amethyst_servo::{Anchor, ServoBlit};
+
+#[test]
+fn draws_pages_at_their_own_size_by_default() {
+    let blit = ServoBlit::default();
+    assert_eq!(blit.screen_rect((100, 50), (32, 16)), (0., 0., 32., 16.));
+}
+
+#[test]
+fn offsets_blits_from_their_anchor() {
+    let blit = ServoBlit {
+        position: (-5., -5.),
+        anchor: Anchor::BottomRight,
+        ..ServoBlit::default()
+    };
+    assert_eq!(blit.screen_rect((100, 50), (20, 10)), (75., 35., 20., 10.));
+    let blit = ServoBlit {
+        size: Some((50., 20.)),
+        anchor: Anchor::Middle,
+        ..ServoBlit::default()
+    };
+    assert_eq!(blit.screen_rect((100, 100), (20, 10)), (25., 40., 50., 20.));
+}
+
+#[test]
+fn maps_the_cursor_onto_page_pixels() {
+    let blit = ServoBlit {
+        position: (10., 10.),
+        size: Some((64., 32.)),
+        ..ServoBlit::default()
+    };
+    assert_eq!(blit.page_position((10., 10.), (100, 100), (32, 16)), Some((0., 0.)));
+    assert_eq!(blit.page_position((42., 26.), (100, 100), (32, 16)), Some((16., 8.)));
+}
+
+#[test]
+fn ignores_the_cursor_outside_the_page() {
+    let blit = ServoBlit {
+        size: Some((64., 32.)),
+        ..ServoBlit::default()
+    };
+    assert_eq!(blit.page_position((64., 0.), (100, 100), (32, 16)), None);
+    assert_eq!(blit.page_position((0., 32.), (100, 100), (32, 16)), None);
+    assert_eq!(blit.page_position((-1., 0.), (100, 100), (32, 16)), None);
+    let empty = ServoBlit {
+        size: Some((0., 32.)),
+        ..ServoBlit::default()
+    };
+    assert_eq!(empty.page_position((0., 0.), (100, 100), (32, 16)), None);
+}
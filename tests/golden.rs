@@ -0,0 +1,95 @@
+extern crate amethyst;
+extern crate amethyst_servo;
+
+use std::time::Duration;
+use amethyst::ecs::Entity;
+use amethyst_servo::{ServoBackground, ServoImage};
+use amethyst_servo::testing::{assert_golden, compare, ServoTestHarness};
+
+fn page(name: &str) -> String {
+    format!("file://{}/tests/pages/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+fn golden(name: &str) -> String {
+    format!("{}/tests/golden/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+fn solid(width: u32, height: u32, color: [u8; 4]) -> ServoImage {
+    ServoImage {
+        width: width,
+        height: height,
+        data: color.iter().cloned().cycle().take((width * height * 4) as usize).collect(),
+    }
+}
+
+// Pages share Servo's compositor, every capture has to show its own page and not the one
+// composited before it
+fn assert_own_pages(harness: &mut ServoTestHarness, pages: &[(Entity, (u32, u32), [u8; 4])]) {
+    for &(page, _, _) in pages {
+        harness
+            .wait_for_load(page, Duration::from_secs(30))
+            .unwrap();
+    }
+    harness.settle(10);
+    for &(page, (width, height), color) in pages.iter().chain(pages.iter()) {
+        let image = harness.capture(page).unwrap();
+        let diff = compare(&image, &solid(width, height, color), 2).unwrap();
+        assert_eq!(diff.mismatched, 0, "page {:?} shows another color", color);
+    }
+}
+
+#[test]
+// No reference has been rendered by Servo yet, run with AMETHYST_SERVO_BLESS=1 to create it
+#[ignore]
+fn solid_background() {
+    let mut harness = ServoTestHarness::new().unwrap();
+    let page = harness.open(&page("solid.html"), 64, 64);
+    harness
+        .wait_for_load(page, Duration::from_secs(30))
+        .unwrap();
+    harness.settle(10);
+    let image = harness.capture(page).unwrap();
+    assert_golden(&image, golden("solid.png"), 2);
+}
+
+#[test]
+// No reference has been rendered by Servo yet, run with AMETHYST_SERVO_BLESS=1 to create it
+#[ignore]
+fn transparent_background() {
+    let mut harness = ServoTestHarness::new().unwrap();
+    let page = harness.open(&page("transparent.html"), 64, 64);
+    harness
+        .world()
+        .write::<ServoBackground>()
+        .insert(page, ServoBackground::transparent());
+    harness
+        .wait_for_load(page, Duration::from_secs(30))
+        .unwrap();
+    harness.settle(10);
+    let image = harness.capture(page).unwrap();
+    assert_golden(&image, golden("transparent.png"), 2);
+}
+
+#[test]
+fn pages_of_the_same_size() {
+    let mut harness = ServoTestHarness::new().unwrap();
+    let red = harness.open(&page("red.html"), 64, 64);
+    let green = harness.open(&page("green.html"), 64, 64);
+    let pages = [
+        (red, (64, 64), [255, 0, 0, 255]),
+        (green, (64, 64), [0, 255, 0, 255]),
+    ];
+    assert_own_pages(&mut harness, &pages);
+}
+
+#[test]
+fn pages_of_different_sizes() {
+    let mut harness = ServoTestHarness::new().unwrap();
+    let red = harness.open(&page("red.html"), 64, 64);
+    let green = harness.open(&page("green.html"), 32, 48);
+    let pages = [
+        (red, (64, 64), [255, 0, 0, 255]),
+        (green, (32, 48), [0, 255, 0, 255]),
+    ];
+    assert_own_pages(&mut harness, &pages);
+}
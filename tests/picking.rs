@@ -0,0 +1,111 @@
+extern crate amethyst;
+extern crate amethyst_servo;
+
+use amethyst::core::cgmath::{Deg, Matrix4, Point3, Vector3};
+use amethyst::core::transform::GlobalTransform;
+use amethyst::renderer::{Camera, Projection};
+use amethyst_servo::ServoRaycastTarget;
+use amethyst_servo::picking::{uv_to_page, Ray};
+
+// Two triangles covering -1..1 on the XY plane facing +Z, texture space has V going up
+fn quad() -> ServoRaycastTarget {
+    ServoRaycastTarget::new(vec![
+        ([-1., -1., 0.], [0., 0.]),
+        ([1., -1., 0.], [1., 0.]),
+        ([1., 1., 0.], [1., 1.]),
+        ([-1., -1., 0.], [0., 0.]),
+        ([1., 1., 0.], [1., 1.]),
+        ([-1., 1., 0.], [0., 1.]),
+    ])
+}
+
+fn at(x: f32, y: f32, z: f32) -> GlobalTransform {
+    GlobalTransform(Matrix4::from_translation([x, y, z].into()).into())
+}
+
+fn ray(origin: [f32; 3], direction: [f32; 3]) -> Ray {
+    Ray {
+        origin: Point3::from(origin),
+        direction: Vector3::from(direction),
+    }
+}
+
+fn camera() -> Camera {
+    Camera::from(Projection::perspective(1., Deg(90.)))
+}
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-3
+}
+
+#[test]
+fn hits_the_quad_at_the_texture_coordinate() {
+    let hit = quad().intersect(&ray([0.5, 0.5, 1.], [0., 0., -1.]), &at(0., 0., 0.));
+    let (distance, uv) = hit.unwrap();
+    assert!(close(distance, 1.));
+    assert!(close(uv[0], 0.75) && close(uv[1], 0.75));
+}
+
+#[test]
+fn measures_the_distance_in_world_space() {
+    let matrix = Matrix4::from_translation([0., 0., -1.].into()) * Matrix4::from_scale(2.);
+    let transform = GlobalTransform(matrix.into());
+    let (distance, uv) = quad()
+        .intersect(&ray([1., 0., 1.], [0., 0., -1.]), &transform)
+        .unwrap();
+    assert!(close(distance, 2.));
+    assert!(close(uv[0], 0.75) && close(uv[1], 0.5));
+}
+
+#[test]
+fn misses_rays_parallel_to_the_quad() {
+    let hit = quad().intersect(&ray([-2., 0., 0.], [1., 0., 0.]), &at(0., 0., 0.));
+    assert_eq!(hit, None);
+}
+
+#[test]
+fn misses_quads_behind_the_ray() {
+    let hit = quad().intersect(&ray([0., 0., -1.], [0., 0., -1.]), &at(0., 0., 0.));
+    assert_eq!(hit, None);
+}
+
+#[test]
+fn misses_rays_beside_the_quad() {
+    let hit = quad().intersect(&ray([1.5, 0., 1.], [0., 0., -1.]), &at(0., 0., 0.));
+    assert_eq!(hit, None);
+}
+
+#[test]
+fn flips_v_into_page_pixels() {
+    assert_eq!(uv_to_page([0.25, 0.25], (200, 100)), (50., 75.));
+    assert_eq!(uv_to_page([0., 1.], (200, 100)), (0., 0.));
+}
+
+#[test]
+fn casts_rays_from_the_camera_through_the_cursor() {
+    let ray = Ray::from_camera((50., 50.), (100, 100), &camera(), &at(0., 0., 2.)).unwrap();
+    assert!(close(ray.direction.x, 0.) && close(ray.direction.y, 0.));
+    assert!(close(ray.direction.z, -1.));
+    let (_, uv) = quad().intersect(&ray, &at(0., 0., 0.)).unwrap();
+    assert!(close(uv[0], 0.5) && close(uv[1], 0.5));
+
+    // A 90 degree camera 2 units away sees -2..2, so the top left quarter is over the quad
+    let ray = Ray::from_camera((37.5, 37.5), (100, 100), &camera(), &at(0., 0., 2.)).unwrap();
+    let (_, uv) = quad().intersect(&ray, &at(0., 0., 0.)).unwrap();
+    assert!(close(uv[0], 0.25) && close(uv[1], 0.75));
+}
+
+#[test]
+fn casts_no_ray_into_an_empty_window() {
+    assert!(Ray::from_camera((0., 0.), (0, 100), &camera(), &at(0., 0., 2.)).is_none());
+}
+
+#[test]
+fn culls_quads_outside_the_view() {
+    let camera = camera();
+    let view = at(0., 0., 2.);
+    assert!(quad().in_view(&camera, &view, &at(0., 0., 0.)));
+    assert!(quad().in_view(&camera, &view, &at(2.5, 0., 0.)));
+    assert!(!quad().in_view(&camera, &view, &at(10., 0., 0.)));
+    assert!(!quad().in_view(&camera, &view, &at(0., 0., 5.)));
+}
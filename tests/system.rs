@@ -0,0 +1,555 @@
+extern crate amethyst;
+extern crate amethyst_servo;
+extern crate servo;
+
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::mem;
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use amethyst::assets::Directory;
+use amethyst::ecs::{DispatcherBuilder, Entity, Fetch, RunNow, System};
+use amethyst::prelude::World;
+use amethyst::renderer::ScreenDimensions;
+use amethyst::shrev::EventChannel;
+use amethyst::winit::{ElementState, Event, KeyboardInput, ModifiersState, MouseButton,
+                      VirtualKeyCode, WindowEvent};
+use amethyst_servo::{JsError, ServoAssets, ServoBlit, ServoError, ServoFocus, ServoFocusSystem,
+                     ServoSize, ServoStatus, ServoUrl, ServoZoom};
+use amethyst_servo::handle::ServoHandle;
+use amethyst_servo::system::ServoUiSystem;
+use amethyst_servo::testing::{test_world, MockBackend, MockCall};
+use servo::embedder_traits::EmbedderMsg;
+use servo::ipc_channel::ipc;
+
+fn setup() -> (World, ServoUiSystem<MockBackend>, MockBackend) {
+    let mut world = test_world().unwrap();
+    let mock = MockBackend::new();
+    let system = ServoUiSystem::with_engine(&mut world, mock.clone());
+    (world, system, mock)
+}
+
+// Every step runs as if Servo had woken the event loop
+fn step(world: &mut World, system: &mut ServoUiSystem<MockBackend>) {
+    world
+        .write_resource::<EventChannel<Event>>()
+        .single_write(Event::Awakened);
+    system.run_now(&world.res);
+    world.maintain();
+}
+
+// Plays the page side of the bridge, returns the body of the response
+fn request(port: u16, method: &str, path: &str, body: &str) -> String {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    ).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response.splitn(2, "\r\n\r\n").nth(1).unwrap_or("").to_string()
+}
+
+const NO_MODIFIERS: ModifiersState = ModifiersState {
+    shift: false,
+    ctrl: false,
+    alt: false,
+    logo: false,
+};
+
+fn key(code: VirtualKeyCode, state: ElementState) -> Event {
+    let input = KeyboardInput {
+        scancode: 0,
+        state: state,
+        virtual_keycode: Some(code),
+        modifiers: NO_MODIFIERS,
+    };
+    // winit only makes ids for real windows and devices, they are plain integers underneath
+    unsafe {
+        Event::WindowEvent {
+            window_id: mem::zeroed(),
+            event: WindowEvent::KeyboardInput {
+                device_id: mem::zeroed(),
+                input: input,
+            },
+        }
+    }
+}
+
+fn cursor(x: f64, y: f64) -> Event {
+    unsafe {
+        Event::WindowEvent {
+            window_id: mem::zeroed(),
+            event: WindowEvent::CursorMoved {
+                device_id: mem::zeroed(),
+                position: (x, y),
+                modifiers: NO_MODIFIERS,
+            },
+        }
+    }
+}
+
+fn button(state: ElementState) -> Event {
+    unsafe {
+        Event::WindowEvent {
+            window_id: mem::zeroed(),
+            event: WindowEvent::MouseInput {
+                device_id: mem::zeroed(),
+                state: state,
+                button: MouseButton::Left,
+                modifiers: NO_MODIFIERS,
+            },
+        }
+    }
+}
+
+// Stands in for a game system reading its key binding
+struct Binding(VirtualKeyCode, Arc<AtomicBool>);
+
+impl<'a> System<'a> for Binding {
+    type SystemData = Fetch<'a, ServoFocus>;
+
+    fn run(&mut self, focus: Self::SystemData) {
+        self.1.store(focus.consumed(self.0), Ordering::SeqCst);
+    }
+}
+
+fn open(world: &mut World, url: &str) -> Entity {
+    world
+        .create_entity()
+        .with(ServoUrl::from(url))
+        .with(ServoSize::new(32, 16).with_device_pixel_ratio(1.))
+        .build()
+}
+
+#[test]
+fn starts_a_browser_once_per_url() {
+    let (mut world, mut system, mock) = setup();
+    let page = open(&mut world, "http://localhost/");
+    step(&mut world, &mut system);
+    step(&mut world, &mut system);
+    let calls = mock.take_calls();
+    assert_eq!(calls[0], MockCall::NewBrowser("http://localhost/".into()));
+    let started = calls.iter().filter(|call| match call {
+        &&MockCall::NewBrowser(_) => true,
+        _ => false,
+    });
+    assert_eq!(started.count(), 1);
+    assert!(!world.read::<ServoUrl>().get(page).unwrap().dirty);
+    assert!(!world.read::<ServoSize>().get(page).unwrap().dirty);
+    assert!(calls.contains(&MockCall::SetupOffscreenTarget));
+    assert!(calls.contains(&MockCall::Resize(mock.browsers()[0], (32, 16))));
+}
+
+#[test]
+fn navigates_when_the_url_changes() {
+    let (mut world, mut system, mock) = setup();
+    let page = open(&mut world, "http://localhost/");
+    step(&mut world, &mut system);
+    let id = mock.browsers()[0];
+    mock.take_calls();
+    world
+        .write::<ServoUrl>()
+        .get_mut(page)
+        .unwrap()
+        .goto("http://localhost/next".into());
+    step(&mut world, &mut system);
+    let calls = mock.take_calls();
+    assert!(calls.contains(&MockCall::LoadUrl(id, "http://localhost/next".into())));
+    assert!(!calls.contains(&MockCall::NewBrowser("http://localhost/next".into())));
+}
+
+#[test]
+fn resizes_when_the_size_changes() {
+    let (mut world, mut system, mock) = setup();
+    let page = open(&mut world, "http://localhost/");
+    step(&mut world, &mut system);
+    let id = mock.browsers()[0];
+    mock.take_calls();
+    let size = ServoSize::new(64, 48).with_device_pixel_ratio(1.);
+    world.write::<ServoSize>().insert(page, size);
+    step(&mut world, &mut system);
+    assert!(mock.take_calls().contains(&MockCall::Resize(id, (64, 48))));
+}
+
+#[test]
+fn closes_the_browser_of_deleted_entities() {
+    let (mut world, mut system, mock) = setup();
+    let page = open(&mut world, "http://localhost/");
+    step(&mut world, &mut system);
+    let id = mock.browsers()[0];
+    world.delete_entity(page).unwrap();
+    step(&mut world, &mut system);
+    assert!(mock.take_calls().contains(&MockCall::Close(id)));
+    assert!(mock.browsers().is_empty());
+}
+
+#[test]
+fn reports_browsers_that_fail_to_start() {
+    let (mut world, mut system, mock) = setup();
+    mock.fail_next_browser(ServoError::EngineStartup("mock".into()));
+    let page = open(&mut world, "http://localhost/");
+    step(&mut world, &mut system);
+    assert_eq!(
+        world.read::<ServoStatus>().get(page),
+        Some(&ServoStatus::Failed(ServoError::EngineStartup("mock".into())))
+    );
+    assert!(mock.browsers().is_empty());
+}
+
+#[test]
+fn sets_up_a_failed_target_again_only_when_its_size_changes() {
+    let (mut world, mut system, mock) = setup();
+    mock.fail_next_target(ServoError::GlContext("mock".into()));
+    let page = open(&mut world, "http://localhost/");
+    step(&mut world, &mut system);
+    assert!(mock.take_calls().contains(&MockCall::SetupOffscreenTarget));
+    assert_eq!(
+        world.read::<ServoStatus>().get(page),
+        Some(&ServoStatus::Failed(ServoError::GlContext("mock".into())))
+    );
+    step(&mut world, &mut system);
+    assert!(!mock.take_calls().contains(&MockCall::SetupOffscreenTarget));
+    let size = ServoSize::new(64, 48).with_device_pixel_ratio(1.);
+    world.write::<ServoSize>().insert(page, size);
+    step(&mut world, &mut system);
+    assert!(mock.take_calls().contains(&MockCall::SetupOffscreenTarget));
+}
+
+#[test]
+fn routes_engine_messages_to_their_entity() {
+    let (mut world, mut system, mock) = setup();
+    let first = open(&mut world, "http://localhost/first");
+    let second = open(&mut world, "http://localhost/second");
+    step(&mut world, &mut system);
+    let id = world.read::<ServoHandle>().get(second).unwrap().id;
+    mock.send(Some(id), EmbedderMsg::LoadComplete);
+    step(&mut world, &mut system);
+    let statuses = world.read::<ServoStatus>();
+    assert_eq!(statuses.get(first), Some(&ServoStatus::Loading));
+    assert_eq!(statuses.get(second), Some(&ServoStatus::Loaded));
+}
+
+#[test]
+fn only_composites_pages_servo_reported_on() {
+    let (mut world, mut system, mock) = setup();
+    let first = open(&mut world, "http://localhost/first");
+    let second = open(&mut world, "http://localhost/second");
+    step(&mut world, &mut system);
+    step(&mut world, &mut system);
+    mock.take_calls();
+    let (first, second) = {
+        let handles = world.read::<ServoHandle>();
+        (handles.get(first).unwrap().id, handles.get(second).unwrap().id)
+    };
+    mock.send(Some(second), EmbedderMsg::LoadComplete);
+    step(&mut world, &mut system);
+    step(&mut world, &mut system);
+    let calls = mock.take_calls();
+    assert!(calls.contains(&MockCall::Composite(second)));
+    assert!(!calls.contains(&MockCall::Composite(first)));
+}
+
+#[test]
+fn releases_the_mouse_on_the_page_it_was_pressed_on() {
+    let (mut world, mut system, mock) = setup();
+    world.add_resource(ScreenDimensions::new(128, 64));
+    let left = open(&mut world, "http://localhost/left");
+    let right = open(&mut world, "http://localhost/right");
+    world.write::<ServoBlit>().insert(left, ServoBlit::default());
+    let blit = ServoBlit {
+        position: (64., 0.),
+        ..ServoBlit::default()
+    };
+    world.write::<ServoBlit>().insert(right, blit);
+    step(&mut world, &mut system);
+    let (left, right) = {
+        let handles = world.read::<ServoHandle>();
+        (handles.get(left).unwrap().id, handles.get(right).unwrap().id)
+    };
+    for event in vec![cursor(8., 8.), button(ElementState::Pressed), cursor(72., 8.)] {
+        world.write_resource::<EventChannel<Event>>().single_write(event);
+    }
+    step(&mut world, &mut system);
+    assert!(mock.take_calls().contains(&MockCall::Event(left)));
+    world
+        .write_resource::<EventChannel<Event>>()
+        .single_write(button(ElementState::Released));
+    step(&mut world, &mut system);
+    let calls = mock.take_calls();
+    assert!(calls.contains(&MockCall::Event(left)));
+    assert!(!calls.contains(&MockCall::Event(right)));
+}
+
+#[test]
+fn lets_the_cursor_through_blits_without_input() {
+    let (mut world, mut system, mock) = setup();
+    world.add_resource(ScreenDimensions::new(128, 64));
+    let below = open(&mut world, "http://localhost/below");
+    let above = open(&mut world, "http://localhost/above");
+    world.write::<ServoBlit>().insert(below, ServoBlit::default());
+    let blit = ServoBlit {
+        z: 1.,
+        input: false,
+        ..ServoBlit::default()
+    };
+    world.write::<ServoBlit>().insert(above, blit);
+    step(&mut world, &mut system);
+    let (below, above) = {
+        let handles = world.read::<ServoHandle>();
+        (handles.get(below).unwrap().id, handles.get(above).unwrap().id)
+    };
+    mock.take_calls();
+    world
+        .write_resource::<EventChannel<Event>>()
+        .single_write(cursor(8., 8.));
+    step(&mut world, &mut system);
+    let calls = mock.take_calls();
+    assert!(calls.contains(&MockCall::Event(below)));
+    assert!(!calls.contains(&MockCall::Event(above)));
+}
+
+#[test]
+fn zooms_and_reports_the_clamped_zoom() {
+    let (mut world, mut system, mock) = setup();
+    let page = open(&mut world, "http://localhost/");
+    step(&mut world, &mut system);
+    let id = mock.browsers()[0];
+    mock.take_calls();
+    world.write::<ServoZoom>().insert(page, ServoZoom::new(20.));
+    step(&mut world, &mut system);
+    assert!(mock.take_calls().contains(&MockCall::Zoom(id, 8.)));
+    assert_eq!(world.read::<ServoZoom>().get(page).unwrap().page_zoom(), 8.);
+    world.write::<ServoZoom>().get_mut(page).unwrap().reset();
+    step(&mut world, &mut system);
+    assert!(mock.take_calls().contains(&MockCall::ResetZoom(id)));
+}
+
+#[test]
+fn pinch_zoom_stays_within_the_viewport() {
+    let (mut world, mut system, mock) = setup();
+    let page = open(&mut world, "http://localhost/");
+    step(&mut world, &mut system);
+    let id = mock.browsers()[0];
+    world.write::<ServoZoom>().insert(page, ServoZoom::new(1.));
+    world.write::<ServoZoom>().get_mut(page).unwrap().pinch = 2.;
+    step(&mut world, &mut system);
+    assert!(mock.take_calls().contains(&MockCall::PinchZoom(id, 2.)));
+    world.write::<ServoZoom>().get_mut(page).unwrap().pinch = 0.5;
+    step(&mut world, &mut system);
+    assert!(mock.take_calls().contains(&MockCall::PinchZoom(id, 0.5)));
+    assert_eq!(world.read::<ServoZoom>().get(page).unwrap().pinch_zoom(), 1.);
+    world.write::<ServoZoom>().get_mut(page).unwrap().pinch = 4.;
+    step(&mut world, &mut system);
+    world.write::<ServoZoom>().get_mut(page).unwrap().reset();
+    step(&mut world, &mut system);
+    assert!(mock.take_calls().contains(&MockCall::PinchZoom(id, 0.25)));
+    assert_eq!(world.read::<ServoZoom>().get(page).unwrap().pinch_zoom(), 1.);
+}
+
+#[test]
+fn restores_the_size_and_zoom_of_each_browser_it_switches_to() {
+    let (mut world, mut system, mock) = setup();
+    let small = open(&mut world, "http://localhost/small");
+    let large = open(&mut world, "http://localhost/large");
+    let size = ServoSize::new(64, 48).with_device_pixel_ratio(1.);
+    world.write::<ServoSize>().insert(large, size);
+    world.write::<ServoZoom>().insert(small, ServoZoom::new(2.));
+    step(&mut world, &mut system);
+    let ids = {
+        let handles = world.read::<ServoHandle>();
+        (handles.get(small).unwrap().id, handles.get(large).unwrap().id)
+    };
+    mock.take_calls();
+    let reload = |world: &mut World, page: Entity| {
+        world.write::<ServoHandle>().get_mut(page).unwrap().reload();
+    };
+    reload(&mut world, small);
+    reload(&mut world, large);
+    step(&mut world, &mut system);
+    let calls = mock.take_calls();
+    assert!(calls.contains(&MockCall::Select(ids.0)));
+    assert!(calls.contains(&MockCall::Resize(ids.0, (32, 16))));
+    assert!(calls.contains(&MockCall::Zoom(ids.0, 2.)));
+    assert!(calls.contains(&MockCall::Select(ids.1)));
+    assert!(calls.contains(&MockCall::Resize(ids.1, (64, 48))));
+    assert!(calls.contains(&MockCall::ResetZoom(ids.1)));
+
+    // Staying on the same browser needs neither
+    reload(&mut world, large);
+    step(&mut world, &mut system);
+    let calls = mock.take_calls();
+    assert!(calls.contains(&MockCall::Event(ids.1)));
+    assert!(!calls.contains(&MockCall::Select(ids.1)));
+    assert!(!calls.contains(&MockCall::Resize(ids.1, (64, 48))));
+}
+
+#[test]
+fn reloads_changed_asset_stylesheets_by_their_page_url() {
+    let dir = env::temp_dir().join("amethyst_servo_hot_reload");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("page.html"), "<link rel=stylesheet href=style.css>").unwrap();
+    fs::write(dir.join("style.css"), "body { color: red; }").unwrap();
+    let mut world = test_world().unwrap();
+    let assets = ServoAssets::new().with_default_source(Directory::new(&dir));
+    let mock = MockBackend::with_bridge(&assets).unwrap();
+    let port = mock.bridge_port().unwrap();
+    world.add_resource(assets);
+    let mut system = ServoUiSystem::with_engine(&mut world, mock.clone()).with_hot_reload(true);
+    open(&mut world, "amethyst:///page.html");
+    step(&mut world, &mut system);
+    let id = mock.browsers()[0];
+
+    // The injected script introduces the page and reports what it loaded
+    let base = format!("http://127.0.0.1:{}/assets/", port);
+    let page = servo::servo_url::ServoUrl::parse(&format!("{}/page.html", base)).unwrap();
+    mock.send(Some(id), EmbedderMsg::HistoryChanged(vec![page], 0));
+    let path = format!("{}/page", mock.bridge_secret().unwrap());
+    let (sender, _receiver) = ipc::channel().unwrap();
+    let connect = format!("amethyst-bridge:connect/{}", path);
+    let connect = servo::servo_url::ServoUrl::parse(&connect).unwrap();
+    mock.send(Some(id), EmbedderMsg::AllowNavigation(connect, sender));
+    step(&mut world, &mut system);
+    let resources = format!(
+        r#"{{"type":"resources","urls":["{0}/page.html","{0}/style.css"]}}"#,
+        base
+    );
+    request(port, "POST", &format!("/send/{}", path), &resources);
+    step(&mut world, &mut system);
+    mock.take_calls();
+
+    // Modification times have a resolution of a second
+    thread::sleep(Duration::from_millis(1100));
+    fs::write(dir.join("style.css"), "body { color: green; }").unwrap();
+    step(&mut world, &mut system);
+    let commands = request(port, "GET", &format!("/poll/{}", path), "");
+    assert!(commands.contains(r#""type":"reload-css""#));
+    assert!(commands.contains(&format!(r#""{}/style.css""#, base)));
+    assert!(!mock.take_calls().contains(&MockCall::Event(id)));
+}
+
+#[test]
+fn fails_scripts_of_pages_that_never_connect() {
+    let mut world = test_world().unwrap();
+    let mock = MockBackend::with_bridge(&ServoAssets::new()).unwrap();
+    let mut system = ServoUiSystem::with_engine(&mut world, mock.clone());
+    let page = open(&mut world, "http://localhost/");
+    step(&mut world, &mut system);
+    let id = mock.browsers()[0];
+    let result = world
+        .write::<ServoHandle>()
+        .get_mut(page)
+        .unwrap()
+        .evaluate_js("1 + 1");
+    step(&mut world, &mut system);
+    assert_eq!(result.try_get(), None);
+    mock.send(Some(id), EmbedderMsg::LoadComplete);
+    step(&mut world, &mut system);
+    assert_eq!(result.try_get(), Some(Err(JsError::Unavailable)));
+    assert_eq!(result.try_get(), Some(Err(JsError::Unavailable)));
+    let called = Arc::new(AtomicBool::new(false));
+    let flag = called.clone();
+    result.then(move |result| flag.store(result.is_err(), Ordering::SeqCst));
+    assert!(called.load(Ordering::SeqCst));
+}
+
+#[test]
+fn fails_scripts_meant_for_a_page_that_navigated() {
+    let mut world = test_world().unwrap();
+    let mock = MockBackend::with_bridge(&ServoAssets::new()).unwrap();
+    let mut system = ServoUiSystem::with_engine(&mut world, mock.clone());
+    let page = open(&mut world, "file:///page.html");
+    step(&mut world, &mut system);
+    let id = mock.browsers()[0];
+    let url = servo::servo_url::ServoUrl::parse("file:///page.html").unwrap();
+    mock.send(Some(id), EmbedderMsg::HistoryChanged(vec![url], 0));
+    let connect = format!("amethyst-bridge:connect/{}/page", mock.bridge_secret().unwrap());
+    let connect = servo::servo_url::ServoUrl::parse(&connect).unwrap();
+    let (sender, _receiver) = ipc::channel().unwrap();
+    mock.send(Some(id), EmbedderMsg::AllowNavigation(connect, sender));
+    mock.send(Some(id), EmbedderMsg::LoadComplete);
+    step(&mut world, &mut system);
+    let evaluate = |world: &mut World| {
+        world
+            .write::<ServoHandle>()
+            .get_mut(page)
+            .unwrap()
+            .evaluate_js("1 + 1")
+    };
+    let sent = evaluate(&mut world);
+    step(&mut world, &mut system);
+    let queued = evaluate(&mut world);
+    mock.send(Some(id), EmbedderMsg::LoadStart);
+    step(&mut world, &mut system);
+    assert_eq!(sent.try_get(), Some(Err(JsError::Navigated)));
+    assert_eq!(queued.try_get(), Some(Err(JsError::Navigated)));
+}
+
+#[test]
+fn consumes_keys_before_the_game_reads_them() {
+    let (mut world, mut system, _) = setup();
+    let page = open(&mut world, "http://localhost/");
+    step(&mut world, &mut system);
+    let consumed = Arc::new(AtomicBool::new(false));
+    let mut dispatcher = DispatcherBuilder::new()
+        .add(ServoFocusSystem::new(&mut world), "servo_focus", &[])
+        .add(Binding(VirtualKeyCode::A, consumed.clone()), "binding", &["servo_focus"])
+        .build();
+    let mut press = |world: &mut World, state| {
+        world
+            .write_resource::<EventChannel<Event>>()
+            .single_write(key(VirtualKeyCode::A, state));
+        dispatcher.dispatch(&world.res);
+        consumed.load(Ordering::SeqCst)
+    };
+    assert!(!press(&mut world, ElementState::Pressed));
+    assert!(!press(&mut world, ElementState::Released));
+    world.write_resource::<ServoFocus>().focus(page);
+    assert!(press(&mut world, ElementState::Pressed));
+    assert!(!press(&mut world, ElementState::Released));
+}
+
+#[test]
+fn leaves_keys_servo_has_no_name_for_to_the_game() {
+    let (mut world, mut system, _) = setup();
+    let page = open(&mut world, "http://localhost/");
+    step(&mut world, &mut system);
+    let consumed = Arc::new(AtomicBool::new(false));
+    let mut dispatcher = DispatcherBuilder::new()
+        .add(ServoFocusSystem::new(&mut world), "servo_focus", &[])
+        .add(Binding(VirtualKeyCode::F13, consumed.clone()), "binding", &["servo_focus"])
+        .build();
+    world.write_resource::<ServoFocus>().focus(page);
+    world
+        .write_resource::<EventChannel<Event>>()
+        .single_write(key(VirtualKeyCode::F13, ElementState::Pressed));
+    dispatcher.dispatch(&world.res);
+    assert!(!consumed.load(Ordering::SeqCst));
+}
+
+#[test]
+fn blurs_pages_without_a_browser() {
+    let (mut world, mut system, mock) = setup();
+    let page = open(&mut world, "http://localhost/");
+    step(&mut world, &mut system);
+    world.write_resource::<ServoFocus>().focus(page);
+    step(&mut world, &mut system);
+    assert!(world.read_resource::<ServoFocus>().has_keyboard());
+    world.delete_entity(page).unwrap();
+    step(&mut world, &mut system);
+    assert_eq!(world.read_resource::<ServoFocus>().entity, None);
+
+    mock.fail_next_browser(ServoError::EngineStartup("mock".into()));
+    let failed = open(&mut world, "http://localhost/");
+    world.write_resource::<ServoFocus>().focus(failed);
+    step(&mut world, &mut system);
+    assert!(!world.read_resource::<ServoFocus>().has_keyboard());
+}